@@ -0,0 +1,278 @@
+//! This module keeps downloaded pages and API responses on disk
+//! across runs.
+//!
+//! Pages are stored under the id of their chapter and their
+//! MangaDex@Home filename. A journal next to them records which
+//! chapters were downloaded completely, so that running the same
+//! download again skips finished chapters without a single request,
+//! and only fetches the missing pages of the chapter that was
+//! interrupted.
+//!
+//! API responses are stored by path and reused until they are older
+//! than the [`CacheTtl`] of their endpoint. Covers are stored by
+//! filename, which changes whenever the cover does, so they never
+//! expire. In offline mode, every stored response is used whatever
+//! its age.
+extern crate serde_json;
+
+use log::warn;
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use crate::error::DexError;
+use crate::manga::{ImageData, MangaImage, Quality};
+
+/// The name of the journal file inside the cache directory
+const JOURNAL_FILE: &str = "journal.json";
+
+/// The chapters that were downloaded completely, with the
+/// filenames of their pages in order and the quality they were
+/// downloaded in.
+#[derive(Default, Serialize, Deserialize)]
+struct Journal {
+    chapters: HashMap<String, Vec<String>>,
+    /// Chapters journaled before qualities were recorded have none,
+    /// and are checked against their server again
+    #[serde(default)]
+    qualities: HashMap<String, Quality>,
+}
+
+/// A directory of downloaded pages and the journal of the
+/// chapters they belong to.
+pub struct PageCache {
+    root: PathBuf,
+    journal: Mutex<Journal>,
+}
+
+impl PageCache {
+    /// Opens the cache in a directory, creating it if needed.
+    ///
+    /// A journal that cannot be read is started over, since the
+    /// pages it refers to are found again as they are requested.
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self, DexError> {
+        let root = root.into();
+        fs::create_dir_all(&root)?;
+
+        let journal_path = root.join(JOURNAL_FILE);
+        let journal = match fs::read(&journal_path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                warn!("Ignoring unreadable journal {}: {}", journal_path.display(), e);
+                Journal::default()
+            }),
+            Err(_) => Journal::default(),
+        };
+
+        Ok(PageCache { root, journal: Mutex::new(journal) })
+    }
+
+    /// Returns the directory of the cache
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Returns where a page of a chapter is stored
+    pub fn page_path(&self, chapter_id: &str, file_name: &str) -> PathBuf {
+        self.root.join("pages").join(chapter_id).join(file_name)
+    }
+
+    /// Returns the stored page of a chapter, if it was downloaded before
+    pub fn get(&self, chapter_id: &str, file_name: &str, page_no: i32) -> Option<MangaImage> {
+        let path = self.page_path(chapter_id, file_name);
+        if path.is_file() {
+            Some(MangaImage::from_file(page_no, path))
+        } else {
+            None
+        }
+    }
+
+    /// Stores a downloaded page and returns it backed by its
+    /// file in the cache.
+    pub async fn store(&self, chapter_id: &str, file_name: &str, image: MangaImage) -> Result<MangaImage, DexError> {
+        store_image(self.page_path(chapter_id, file_name), image).await
+    }
+
+    /// Returns every page of a chapter if it was downloaded
+    /// completely before, in a quality that meets the one asked
+    /// for, and none of its pages went missing since.
+    /// 
+    /// Only original pages meet [`Quality::Original`], while any
+    /// pages meet the other qualities.
+    pub fn completed(&self, chapter_id: &str, quality: Quality) -> Option<Vec<MangaImage>> {
+        let journal = self.journal.lock().unwrap();
+        let files = journal.chapters.get(chapter_id)?;
+        let downloaded = journal.qualities.get(chapter_id)?;
+        if quality == Quality::Original && *downloaded != Quality::Original {
+            return None;
+        }
+
+        files.iter()
+            .enumerate()
+            .map(|(page_no, file_name)| self.get(chapter_id, file_name, page_no as i32))
+            .collect()
+    }
+
+    /// Records that every page of a chapter is stored, with the
+    /// filenames of its pages in order and the quality they were
+    /// downloaded in, where [`Quality::OriginalWithFallback`] means
+    /// that some of the pages are compressed.
+    pub fn complete(&self, chapter_id: &str, files: Vec<String>, quality: Quality) -> Result<(), DexError> {
+        let mut journal = self.journal.lock().unwrap();
+        journal.chapters.insert(String::from(chapter_id), files);
+        journal.qualities.insert(String::from(chapter_id), quality);
+
+        let bytes = serde_json::to_vec(&*journal)
+            .map_err(|e| DexError::Io(std::io::Error::other(e)))?;
+        let path = self.root.join(JOURNAL_FILE);
+        let partial = path.with_extension("part");
+        fs::write(&partial, bytes)?;
+        fs::rename(&partial, &path)?;
+        Ok(())
+    }
+}
+
+/// How long the responses of each API endpoint are reused.
+///
+/// A zero duration turns caching off for the endpoint.
+#[derive(Clone, Debug)]
+pub struct CacheTtl {
+    /// `/manga/{id}`
+    pub manga: Duration,
+    /// `/author/{id}`
+    pub author: Duration,
+    /// `/cover/{id}`
+    pub cover: Duration,
+    /// The chapter lists of `/chapter` and `/manga/{id}/feed`
+    pub chapters: Duration,
+    /// `/at-home/server/{id}`, whose tokens expire after a few minutes
+    pub at_home: Duration,
+}
+
+impl Default for CacheTtl {
+    fn default() -> Self {
+        CacheTtl {
+            manga: Duration::from_secs(24 * 60 * 60),
+            author: Duration::from_secs(7 * 24 * 60 * 60),
+            cover: Duration::from_secs(7 * 24 * 60 * 60),
+            chapters: Duration::from_secs(10 * 60),
+            at_home: Duration::ZERO,
+        }
+    }
+}
+
+impl CacheTtl {
+    /// Returns the TTL of the endpoint a path of the API belongs to
+    pub fn for_path(&self, path: &str) -> Duration {
+        let path = path.split('?').next().unwrap_or(path);
+        if path.starts_with("/at-home/server/") {
+            self.at_home
+        } else if path.starts_with("/chapter") || path.ends_with("/feed") {
+            self.chapters
+        } else if path.starts_with("/manga/") {
+            self.manga
+        } else if path.starts_with("/author/") {
+            self.author
+        } else if path.starts_with("/cover/") {
+            self.cover
+        } else {
+            Duration::ZERO
+        }
+    }
+}
+
+/// A directory of API responses and covers.
+pub struct ResponseCache {
+    root: PathBuf,
+}
+
+impl ResponseCache {
+    /// Opens the cache in a directory, creating it if needed
+    pub fn open(root: impl Into<PathBuf>) -> Result<Self, DexError> {
+        let root = root.into();
+        fs::create_dir_all(root.join("responses"))?;
+        Ok(ResponseCache { root })
+    }
+
+    /// Returns where the response of an API path is stored
+    pub fn response_path(&self, path: &str) -> PathBuf {
+        let key = Sha256::digest(path.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+        self.root.join("responses").join(format!("{}.json", key))
+    }
+
+    /// Returns the stored response of an API path, unless it is
+    /// older than `max_age`. Without a `max_age`, any stored
+    /// response is returned.
+    pub async fn get(&self, path: &str, max_age: Option<Duration>) -> Option<Vec<u8>> {
+        let path = self.response_path(path);
+        if let Some(max_age) = max_age {
+            let modified = tokio::fs::metadata(&path).await.ok()?.modified().ok()?;
+            let age = SystemTime::now().duration_since(modified).unwrap_or_default();
+            if age > max_age {
+                return None;
+            }
+        }
+        tokio::fs::read(&path).await.ok()
+    }
+
+    /// Stores the response of an API path
+    pub async fn put(&self, path: &str, body: &[u8]) -> Result<(), DexError> {
+        let path = self.response_path(path);
+        let partial = path.with_extension("part");
+        tokio::fs::write(&partial, body).await?;
+        tokio::fs::rename(&partial, &path).await?;
+        Ok(())
+    }
+
+    /// Returns where a cover of a manga is stored
+    pub fn cover_path(&self, manga_id: &str, file_name: &str) -> PathBuf {
+        self.root.join("covers").join(manga_id).join(file_name)
+    }
+
+    /// Returns the stored cover of a manga, if it was downloaded before
+    pub fn cover(&self, manga_id: &str, file_name: &str) -> Option<MangaImage> {
+        let path = self.cover_path(manga_id, file_name);
+        if path.is_file() {
+            Some(MangaImage::from_file(0, path))
+        } else {
+            None
+        }
+    }
+
+    /// Stores a downloaded cover and returns it backed by its
+    /// file in the cache.
+    pub async fn store_cover(&self, manga_id: &str, file_name: &str, image: MangaImage) -> Result<MangaImage, DexError> {
+        store_image(self.cover_path(manga_id, file_name), image).await
+    }
+}
+
+/// Writes an image to its place in a cache and returns it backed
+/// by that file.
+async fn store_image(path: PathBuf, mut image: MangaImage) -> Result<MangaImage, DexError> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    // Writing next to the image first so that it is never half written
+    let partial = path.with_extension("part");
+    match image.take() {
+        ImageData::Memory(bytes) => tokio::fs::write(&partial, bytes).await?,
+        ImageData::File(spooled) => {
+            // Spooled images are moved, or copied across filesystems
+            if tokio::fs::rename(&spooled, &partial).await.is_err() {
+                tokio::fs::copy(&spooled, &partial).await?;
+                let _ = tokio::fs::remove_file(&spooled).await;
+            }
+        },
+    }
+    tokio::fs::rename(&partial, &path).await?;
+
+    Ok(MangaImage::from_file(image.page_no, path))
+}
@@ -0,0 +1,158 @@
+//! This module records HTTP sessions to cassettes and replays them.
+//!
+//! A cassette is a file with one JSON interaction per line, holding
+//! the URL, status, headers and body of a response. Recording writes
+//! every response received through [`AsyncGet`](crate::connection::AsyncGet)
+//! and the image downloads of [`connection`](crate::connection) as it
+//! arrives, so that a cassette survives an interrupted run. Replaying
+//! serves those responses in the order they were recorded without
+//! touching the network.
+extern crate base64;
+extern crate serde_json;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use reqwest::StatusCode;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::{Serialize, Deserialize};
+
+use std::collections::{HashMap, VecDeque};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use crate::connection::RawResponse;
+use crate::error::DexError;
+
+/// Whether a client records its session or replays a recorded one.
+#[derive(Clone, Debug)]
+pub enum CassetteMode {
+    /// Every response is written to the file, replacing its contents
+    Record(PathBuf),
+    /// Responses are served from the file and nothing is sent
+    Replay(PathBuf),
+}
+
+/// A single recorded response.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    pub method: String,
+    pub url: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    /// The body, encoded in base64
+    pub body: String,
+}
+
+impl Interaction {
+    fn from_response(method: &str, url: &str, response: &RawResponse) -> Self {
+        let headers = response.headers.iter()
+            .filter_map(|(name, value)| {
+                value.to_str().ok().map(|value| (name.to_string(), String::from(value)))
+            })
+            .collect();
+
+        Interaction {
+            method: String::from(method),
+            url: String::from(url),
+            status: response.status.as_u16(),
+            headers,
+            body: STANDARD.encode(&response.body),
+        }
+    }
+
+    fn to_response(&self) -> Result<RawResponse, DexError> {
+        let invalid = |reason: String| DexError::Cassette(format!("{}: {}", &self.url, reason));
+
+        let status = StatusCode::from_u16(self.status)
+            .map_err(|e| invalid(e.to_string()))?;
+        let mut headers = HeaderMap::new();
+        for (name, value) in self.headers.iter() {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| invalid(e.to_string()))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|e| invalid(e.to_string()))?;
+            headers.append(name, value);
+        }
+        let body = STANDARD.decode(&self.body)
+            .map_err(|e| invalid(e.to_string()))?;
+
+        Ok(RawResponse { status, headers, body })
+    }
+}
+
+/// A cassette being recorded or replayed by a client.
+pub enum Cassette {
+    /// The file interactions are appended to
+    Recording(Mutex<File>),
+    /// The recorded interactions of each request, in order
+    Replaying(Mutex<HashMap<String, VecDeque<Interaction>>>),
+}
+
+impl Cassette {
+    /// Creates the cassette file to record to, or loads the one
+    /// to replay.
+    pub fn open(mode: &CassetteMode) -> Result<Self, DexError> {
+        match mode {
+            CassetteMode::Record(path) => Ok(Cassette::Recording(Mutex::new(File::create(path)?))),
+            CassetteMode::Replay(path) => {
+                let mut interactions: HashMap<String, VecDeque<Interaction>> = HashMap::new();
+                for (i, line) in fs::read_to_string(path)?.lines().enumerate() {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let interaction: Interaction = serde_json::from_str(line)
+                        .map_err(|e| DexError::Cassette(format!("line {}: {}", i + 1, e)))?;
+                    interactions.entry(key(&interaction.method, &interaction.url))
+                        .or_default()
+                        .push_back(interaction);
+                }
+                Ok(Cassette::Replaying(Mutex::new(interactions)))
+            },
+        }
+    }
+
+    /// Whether the cassette is served instead of the network
+    pub fn is_replaying(&self) -> bool {
+        matches!(self, Cassette::Replaying(_))
+    }
+
+    /// Appends a response to a recording cassette
+    pub fn record(&self, method: &str, url: &str, response: &RawResponse) -> Result<(), DexError> {
+        if let Cassette::Recording(file) = self {
+            let interaction = Interaction::from_response(method, url, response);
+            let mut line = serde_json::to_vec(&interaction)
+                .map_err(|e| DexError::Cassette(e.to_string()))?;
+            line.push(b'\n');
+            file.lock().unwrap().write_all(&line)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the next recorded response to a request.
+    ///
+    /// Requests made more often than they were recorded are
+    /// answered with their last recorded response.
+    pub fn replay(&self, method: &str, url: &str) -> Result<RawResponse, DexError> {
+        let not_recorded = || DexError::NotRecorded(format!("{} {}", method, url));
+        let interactions = match self {
+            Cassette::Replaying(interactions) => interactions,
+            Cassette::Recording(_) => return Err(not_recorded()),
+        };
+
+        let mut interactions = interactions.lock().unwrap();
+        let queue = interactions.get_mut(&key(method, url))
+            .ok_or_else(not_recorded)?;
+        let interaction = if queue.len() > 1 {
+            queue.pop_front()
+        } else {
+            queue.front().cloned()
+        };
+        interaction.ok_or_else(not_recorded)?.to_response()
+    }
+}
+
+fn key(method: &str, url: &str) -> String {
+    format!("{} {}", method, url)
+}
@@ -0,0 +1,425 @@
+//! This module contains the HTTP client shared by every
+//! request the crate makes.
+//!
+//! A single [`DexClient`] holds one connection pool together
+//! with its settings, and is handed to [`Manga`](crate::manga::Manga),
+//! [`Chapter`](crate::manga::Chapter) and the functions in
+//! [`connection`](crate::connection).
+extern crate reqwest;
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{Client, Proxy};
+
+use crate::cache::{CacheTtl, PageCache, ResponseCache};
+use crate::cassette::{Cassette, CassetteMode};
+use crate::error::DexError;
+use crate::manga::Quality;
+use crate::progress::{Progress, ProgressListener};
+use crate::rate_limit::{EndpointClass, RateLimiter, RateLimits};
+use crate::report::{Report, ReportStats, Reporter};
+
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tokio_util::sync::CancellationToken;
+
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+/// The base URL of the MangaDex API
+pub const API_URL: &str = "https://api.mangadex.org";
+
+/// The base URL of the MangaDex upload server, used for covers
+pub const UPLOADS_URL: &str = "https://uploads.mangadex.org";
+
+/// The base URL of the MangaDex@Home network, used for reports
+pub const NETWORK_URL: &str = "https://api.mangadex.network";
+
+/// The settings used to build a [`DexClient`].
+///
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use dexloader::client::{ClientConfig, DexClient};
+///
+/// let config = ClientConfig {
+///     connect_timeout: Some(Duration::from_secs(5)),
+///     proxy: Some(String::from("socks5://127.0.0.1:9050")),
+///     ..ClientConfig::default()
+/// };
+/// let client = DexClient::new(config).unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct ClientConfig {
+    /// Base URL of the API, e.g. `https://api.mangadex.org`
+    pub api_url: String,
+    /// Base URL of the cover upload server
+    pub uploads_url: String,
+    /// Base URL of the MangaDex@Home network
+    pub network_url: String,
+    /// The User-Agent header sent with every request
+    pub user_agent: String,
+    /// Maximum time to wait for a connection to be established
+    pub connect_timeout: Option<Duration>,
+    /// Maximum time to wait for a response to be fully read
+    pub read_timeout: Option<Duration>,
+    /// An HTTP, HTTPS or SOCKS5 proxy URL all requests go through
+    pub proxy: Option<String>,
+    /// Extra headers sent with every request
+    pub headers: Vec<(String, String)>,
+    /// How failed page downloads are retried
+    pub retry: RetryPolicy,
+    /// The request quotas of the API endpoints
+    pub rate_limits: RateLimits,
+    /// The number of images downloaded at the same time
+    pub concurrent_downloads: usize,
+    /// Whether pages are checked against their hash and decoded
+    /// before being accepted, and chapters against the number of
+    /// pages their server lists
+    pub verify_pages: bool,
+    /// The quality pages are downloaded in
+    pub quality: Quality,
+    /// A directory downloaded images are written to instead of
+    /// being kept in memory
+    pub spool_dir: Option<PathBuf>,
+    /// A directory downloaded pages are kept in across runs, so
+    /// that interrupted downloads can be resumed
+    pub cache_dir: Option<PathBuf>,
+    /// How long cached API responses are reused per endpoint
+    pub cache_ttl: CacheTtl,
+    /// Whether everything is served from the cache, without
+    /// making any request
+    pub offline: bool,
+    /// Records every response to a cassette, or replays a
+    /// recorded one instead of using the network
+    pub cassette: Option<CassetteMode>,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        ClientConfig {
+            api_url: String::from(API_URL),
+            uploads_url: String::from(UPLOADS_URL),
+            network_url: String::from(NETWORK_URL),
+            user_agent: format!("dexloader/{}", env!("CARGO_PKG_VERSION")),
+            connect_timeout: Some(Duration::from_secs(10)),
+            read_timeout: Some(Duration::from_secs(60)),
+            proxy: None,
+            headers: Vec::new(),
+            retry: RetryPolicy::default(),
+            rate_limits: RateLimits::default(),
+            concurrent_downloads: 5,
+            verify_pages: true,
+            quality: Quality::default(),
+            spool_dir: None,
+            cache_dir: None,
+            cache_ttl: CacheTtl::default(),
+            offline: false,
+            cassette: None,
+        }
+    }
+}
+
+/// Dictates how failed page downloads are retried.
+/// 
+/// A page is retried `max_retries` times against the same
+/// MangaDex@Home server, waiting exponentially longer between
+/// attempts. Pages that still fail cause a fresh server to be
+/// requested from `/at-home/server/{chapter}`, up to
+/// `max_refreshes` times.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Retries per page against the same server
+    pub max_retries: u32,
+    /// Fresh at-home servers requested for the failed pages
+    pub max_refreshes: u32,
+    /// The delay before the first retry
+    pub base_delay: Duration,
+    /// The upper bound of the delay between retries
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            max_refreshes: 2,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that never retries
+    pub fn none() -> Self {
+        RetryPolicy { max_retries: 0, max_refreshes: 0, ..RetryPolicy::default() }
+    }
+
+    /// Returns how long to wait before the given retry, starting at 1.
+    /// 
+    /// The delay doubles with each attempt and is capped at `max_delay`,
+    /// then jittered to somewhere between half and all of it so that
+    /// concurrent pages do not retry in lockstep.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(16);
+        let delay = self.base_delay
+            .saturating_mul(1 << exponent)
+            .min(self.max_delay);
+        let half = delay / 2;
+        half + half.mul_f64(fastrand::f64())
+    }
+}
+
+/// A cheaply clonable handle to a configured `reqwest::Client`.
+///
+/// Clones share the same connection pool, rate limiter,
+/// download slots, reporter, caches, cassette, cancellation
+/// token and progress listener.
+#[derive(Clone)]
+pub struct DexClient {
+    http: Client,
+    config: Arc<ClientConfig>,
+    limiter: Arc<RateLimiter>,
+    download_slots: Arc<Semaphore>,
+    reporter: Arc<OnceLock<Reporter>>,
+    page_cache: Option<Arc<PageCache>>,
+    response_cache: Option<Arc<ResponseCache>>,
+    cassette: Option<Arc<Cassette>>,
+    cancellation: CancellationToken,
+    progress: Option<Arc<dyn ProgressListener>>,
+}
+
+impl DexClient {
+    /// Builds a client from its settings.
+    ///
+    /// Fails if the proxy URL or one of the extra headers is invalid,
+    /// if the cache directory cannot be created, or if the cassette
+    /// cannot be opened.
+    pub fn new(config: ClientConfig) -> Result<Self, DexError> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in config.headers.iter() {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| DexError::Config(format!("header name {}: {}", name, e)))?;
+            let value = HeaderValue::from_str(value)
+                .map_err(|e| DexError::Config(format!("header {}: {}", name, e)))?;
+            headers.insert(name, value);
+        }
+
+        let mut builder = Client::builder()
+            .user_agent(&config.user_agent)
+            .default_headers(headers);
+
+        if let Some(timeout) = config.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(timeout) = config.read_timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(proxy) = &config.proxy {
+            let proxy = Proxy::all(proxy)
+                .map_err(|e| DexError::Config(format!("proxy {}: {}", proxy, e)))?;
+            builder = builder.proxy(proxy);
+        }
+
+        let http = builder.build()?;
+        let limiter = Arc::new(RateLimiter::new(&config.rate_limits));
+        let download_slots = Arc::new(Semaphore::new(config.concurrent_downloads.max(1)));
+        let (page_cache, response_cache) = match &config.cache_dir {
+            Some(dir) => (
+                Some(Arc::new(PageCache::open(dir)?)),
+                Some(Arc::new(ResponseCache::open(dir)?)),
+            ),
+            None => (None, None),
+        };
+        let cassette = match &config.cassette {
+            Some(mode) => Some(Arc::new(Cassette::open(mode)?)),
+            None => None,
+        };
+        Ok(DexClient {
+            http,
+            config: Arc::new(config),
+            limiter,
+            download_slots,
+            reporter: Arc::new(OnceLock::new()),
+            page_cache,
+            response_cache,
+            cassette,
+            cancellation: CancellationToken::new(),
+            progress: None,
+        })
+    }
+
+    /// Returns the underlying `reqwest::Client`
+    pub fn http(&self) -> &Client {
+        &self.http
+    }
+
+    /// Returns the settings the client was built with
+    pub fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
+    /// Returns the rate limiter shared by the clones of the client
+    pub fn limiter(&self) -> &RateLimiter {
+        &self.limiter
+    }
+
+    /// Returns the page cache, if the client has a
+    /// [`cache_dir`](ClientConfig::cache_dir)
+    pub fn page_cache(&self) -> Option<&PageCache> {
+        self.page_cache.as_deref()
+    }
+
+    /// Returns the cache of API responses and covers, if the client
+    /// has a [`cache_dir`](ClientConfig::cache_dir)
+    pub fn response_cache(&self) -> Option<&ResponseCache> {
+        self.response_cache.as_deref()
+    }
+
+    /// Makes the client stop its work when the given token is
+    /// cancelled, in place of its own token.
+    ///
+    /// # Examples
+    /// ```
+    /// use tokio_util::sync::CancellationToken;
+    /// use dexloader::client::DexClient;
+    ///
+    /// let token = CancellationToken::new();
+    /// let client = DexClient::default().with_cancellation(token.clone());
+    /// token.cancel();
+    /// assert!(client.is_cancelled());
+    /// ```
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = token;
+        self
+    }
+
+    /// Makes the client hand its progress events to a listener
+    pub fn with_progress(mut self, listener: impl ProgressListener + 'static) -> Self {
+        self.progress = Some(Arc::new(listener));
+        self
+    }
+
+    /// Hands a progress event to the client's listener, if any
+    pub fn emit(&self, event: Progress) {
+        if let Some(listener) = &self.progress {
+            listener.on_progress(event);
+        }
+    }
+
+    /// Returns the token that stops the work of the client and
+    /// every clone of it when cancelled.
+    ///
+    /// Pages that are being downloaded are finished and stored, but
+    /// no new page, chapter or epub is started. The interrupted call
+    /// returns [`DexError::Cancelled`].
+    pub fn cancellation(&self) -> &CancellationToken {
+        &self.cancellation
+    }
+
+    /// Whether the client's work was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled()
+    }
+
+    /// Returns an error if the client's work was cancelled
+    pub fn check_cancelled(&self) -> Result<(), DexError> {
+        if self.is_cancelled() { Err(DexError::Cancelled) } else { Ok(()) }
+    }
+
+    /// Returns the cassette the client records to or replays from
+    pub fn cassette(&self) -> Option<&Cassette> {
+        self.cassette.as_deref()
+    }
+
+    /// Waits for a free download slot. The slot is given back
+    /// when the returned permit is dropped.
+    pub async fn download_slot(&self) -> SemaphorePermit<'_> {
+        self.download_slots
+            .acquire()
+            .await
+            .expect("Download slots are never closed")
+    }
+
+    /// Queues a MangaDex@Home report, starting the background
+    /// reporter on first use.
+    /// 
+    /// Nothing is reported while a cassette is replayed.
+    pub fn report(&self, report: Report) {
+        if self.cassette().is_some_and(Cassette::is_replaying) {
+            return;
+        }
+        self.reporter
+            .get_or_init(|| Reporter::spawn(self.http.clone(), self.network_url("/report")))
+            .send(report);
+    }
+
+    /// Waits until every report queued so far has been sent
+    pub async fn flush_reports(&self) {
+        if let Some(reporter) = self.reporter.get() {
+            reporter.flush().await;
+        }
+    }
+
+    /// Returns what happened to the reports queued so far
+    pub fn report_stats(&self) -> ReportStats {
+        self.reporter
+            .get()
+            .map(Reporter::stats)
+            .unwrap_or_default()
+    }
+
+    /// Returns the rate limited class of a URL, or `None` if the
+    /// URL does not point to the API.
+    pub fn endpoint_class(&self, url: &str) -> Option<EndpointClass> {
+        self.api_path(url).map(EndpointClass::from_path)
+    }
+
+    /// Returns the path of a URL within the API, such as
+    /// `/manga/{id}`, or `None` if the URL does not point to the API.
+    pub fn api_path<'a>(&self, url: &'a str) -> Option<&'a str> {
+        url.strip_prefix(self.config.api_url.trim_end_matches('/'))
+    }
+
+    /// Returns where an image downloaded from `url` is spooled, or
+    /// `None` if the client keeps images in memory.
+    ///
+    /// Images are stored under the last two segments of their URL,
+    /// such as `{chapter hash}/{page}` or `{manga id}/{cover}`.
+    pub fn spool_path(&self, url: &str) -> Option<PathBuf> {
+        let spool_dir = self.config.spool_dir.as_ref()?;
+        let path = url.split(['?', '#']).next()?;
+        let mut segments = path.rsplit('/');
+        let file_name = segments.next()?;
+        let parent = segments.next()?;
+
+        let is_safe = |segment: &str| !segment.is_empty() && segment != "." && segment != "..";
+        if !is_safe(file_name) || !is_safe(parent) {
+            return None;
+        }
+        Some(spool_dir.join(parent).join(file_name))
+    }
+
+    /// Joins a path such as `/manga/{id}` onto the API base URL
+    pub fn api_url(&self, path: &str) -> String {
+        format!("{}{}", self.config.api_url.trim_end_matches('/'), path)
+    }
+
+    /// Joins a path onto the cover upload server base URL
+    pub fn uploads_url(&self, path: &str) -> String {
+        format!("{}{}", self.config.uploads_url.trim_end_matches('/'), path)
+    }
+
+    /// Joins a path onto the MangaDex@Home network base URL
+    pub fn network_url(&self, path: &str) -> String {
+        format!("{}{}", self.config.network_url.trim_end_matches('/'), path)
+    }
+}
+
+impl Default for DexClient {
+    /// Builds a client with the default settings.
+    fn default() -> Self {
+        DexClient::new(ClientConfig::default())
+            .expect("Failed to build the default client")
+    }
+}
@@ -8,154 +8,382 @@ extern crate serde;
 
 use tokio::task::{self, JoinHandle};
 use futures::future::join_all;
-use serde::{Serialize, Deserialize};
 use serde::de::DeserializeOwned;
 use async_trait::async_trait;
 
-use std::time::Instant;
+use std::path::Path;
+use std::time::{Duration, Instant};
 
+use log::warn;
+use reqwest::StatusCode;
+use reqwest::header::HeaderMap;
+use sha2::{Digest, Sha256};
+
+use crate::cassette::Cassette;
+use crate::client::DexClient;
+use crate::error::{ApiErrors, DexError};
 use crate::manga::MangaImage;
+use crate::progress::Progress;
+use crate::report::Report;
+
+/// How many times a request answered with 429 is sent before giving up
+const MAX_RATE_LIMITED_ATTEMPTS: u32 = 5;
+
+/// The result of downloading a single page
+pub type ImageResult = Result<MangaImage, DexError>;
 
-/// A struct for containing the report POST request
-#[derive(Serialize, Deserialize)]
-struct ResponseBody {
-    pub url: String,
-    pub success: bool,
-    pub cached: bool,
-    pub bytes: usize,
-    pub duration: u128
+/// A response that was read in full.
+pub struct RawResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
 }
 
+/// Sends a get request and reads its response.
+/// 
+/// A client with a [`Cassette`](crate::cassette::Cassette) records
+/// the response, or answers from the cassette without sending
+/// anything when it replays one.
+pub async fn send_get(client: &DexClient, url: &str) -> Result<RawResponse, DexError> {
+    let cassette = client.cassette();
+    if let Some(cassette) = cassette.filter(|c| c.is_replaying()) {
+        return cassette.replay("GET", url);
+    }
+
+    let res = client.http()
+        .get(url)
+        .send()
+        .await?;
+    let status = res.status();
+    let headers = res.headers().clone();
+    let body = res.bytes().await?.to_vec();
+    let response = RawResponse { status, headers, body };
+
+    if let Some(cassette) = cassette {
+        cassette.record("GET", url, &response)?;
+    }
+    Ok(response)
+}
 
 #[async_trait]
 /// A **data structure** that can make asynchronous get requests
 pub trait AsyncGet {
+    /// Returns the client the requests are made with.
+    fn client(&self) -> &DexClient;
+
     /// Makes an asynchronous get request and parse the result in a json format.
     /// 
     /// The return format can be denoted by the generic.
-    async fn async_get_json<T>(&self, url: &str) -> T 
-        where T: DeserializeOwned
+    /// 
+    /// Requests to the API wait for the client's rate limiter, and
+    /// are sent again after a pause when the API answers with 429.
+    /// Any other non-success status is returned as an error along
+    /// with the errors reported by MangaDex.
+    /// 
+    /// With a [`ResponseCache`](crate::cache::ResponseCache), API
+    /// responses younger than the TTL of their endpoint are served
+    /// from the cache. Offline clients only serve from the cache.
+    async fn async_get_json<T>(&self, url: &str) -> Result<T, DexError>
+        where T: DeserializeOwned + Send
     {
-        let body = reqwest::get(url)
-            .await
-            .expect("Error yeeting a request")
-            .json::<T>()
-            .await
-            .expect("Failed deserializing json");
-        
-        body
+        let client = self.client();
+        let offline = client.config().offline;
+        let cache = client.response_cache().zip(client.api_path(url));
+        let ttl = cache.map_or(Duration::ZERO, |(_, path)| client.config().cache_ttl.for_path(path));
+
+        if let Some((cache, path)) = cache {
+            let cached = if offline {
+                cache.get(path, None).await
+            } else if !ttl.is_zero() {
+                cache.get(path, Some(ttl)).await
+            } else {
+                None
+            };
+            // A stored response that no longer parses is fetched again
+            if let Some(value) = cached.and_then(|body| serde_json::from_slice::<T>(&body).ok()) {
+                return Ok(value);
+            }
+        }
+        if offline {
+            return Err(DexError::Offline(String::from(url)));
+        }
+
+        let class = client.endpoint_class(url);
+        // Replayed responses are not throttled, nor is their rate
+        // limit followed, while recorded 429s are still retried
+        let replaying = client.cassette().is_some_and(Cassette::is_replaying);
+        let mut attempts = 0;
+
+        let res = loop {
+            client.check_cancelled()?;
+            if let Some(class) = class.filter(|_| !replaying) {
+                tokio::select! {
+                    _ = client.cancellation().cancelled() => return Err(DexError::Cancelled),
+                    _ = client.limiter().acquire(class) => {},
+                }
+            }
+
+            let res = send_get(client, url).await?;
+
+            // Only the API is rate limited, other hosts are not retried
+            let class = match class {
+                Some(class) => class,
+                None => break res,
+            };
+            if !replaying {
+                client.limiter().observe(class, res.status, &res.headers);
+            }
+
+            attempts += 1;
+            if res.status != StatusCode::TOO_MANY_REQUESTS || attempts >= MAX_RATE_LIMITED_ATTEMPTS {
+                break res;
+            }
+        };
+
+        let status = res.status;
+        let retry_after = res.headers
+            .get("retry-after")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok())
+            .map(Duration::from_secs);
+        let bytes = res.body;
+
+        match status {
+            s if s.is_success() => {
+                let value = serde_json::from_slice::<T>(&bytes)
+                    .map_err(|source| DexError::Json { url: String::from(url), source })?;
+                if let Some((cache, path)) = cache.filter(|_| !ttl.is_zero()) {
+                    cache.put(path, &bytes).await?;
+                }
+                Ok(value)
+            },
+            StatusCode::NOT_FOUND => Err(DexError::NotFound(String::from(url))),
+            StatusCode::TOO_MANY_REQUESTS => Err(DexError::RateLimited { url: String::from(url), retry_after }),
+            s => Err(DexError::Http {
+                url: String::from(url),
+                status: s.as_u16(),
+                errors: ApiErrors::from_body(&bytes),
+            }),
+        }
     }
 }
 
-/// Makes a get request to fetch an image asynchronously
-pub async fn async_get_image(url: String, page_no: i32, report: bool) -> Result<MangaImage, Box<dyn std::error::Error + Send + Sync>> {
-    let url_clone = url.clone();
+#[async_trait]
+impl AsyncGet for DexClient {
+    fn client(&self) -> &DexClient {
+        self
+    }
+}
+
+/// Makes a get request to fetch an image asynchronously.
+/// 
+/// The download waits for one of the client's download slots,
+/// so that no more than
+/// [`concurrent_downloads`](crate::client::ClientConfig::concurrent_downloads)
+/// images are fetched at the same time.
+/// 
+/// When `report` is set, the outcome is handed to the client's
+/// background [`Reporter`](crate::report::Reporter), including
+/// failures that never produced a response. Images served from
+/// mangadex.org itself are never reported.
+/// 
+/// When the client has a
+/// [`spool_dir`](crate::client::ClientConfig::spool_dir), the image
+/// is written there and returned as a file-backed image.
+/// 
+/// Once the client is cancelled, images still waiting for a slot
+/// return [`DexError::Cancelled`], while the ones being downloaded
+/// are finished.
+pub async fn async_get_image(client: DexClient, url: String, page_no: i32, report: bool) -> Result<MangaImage, DexError> {
+    if client.config().offline {
+        return Err(DexError::Offline(url));
+    }
+
+    // Pages still waiting for a slot are abandoned when cancelled
+    let _slot = tokio::select! {
+        _ = client.cancellation().cancelled() => return Err(DexError::Cancelled),
+        slot = client.download_slot() => slot,
+    };
 
     // Downloading the image and timing it
     let start_time = Instant::now();
-    let res = reqwest::get(url)
-        .await?;
+    let (result, cached) = fetch_image(&client, &url).await;
     let elapsed_time = start_time.elapsed().as_millis();
 
-    // Getting whether the server returned x-cache
-    let headers = res.headers();
-    let cache = match headers.get("x-cache") {
-        Some(hv) => {hv.to_str().unwrap_or("MISS")},
-        None => "MISS",
-    };
-
-    let mut cache_state = false; 
-    if cache == "HIT" {
-        cache_state = true;
+    // Reporting whether the image was successfully downloaded
+    let is_mangadex_url = url.contains("mangadex.org");
+    if report && !is_mangadex_url {
+        client.report(Report {
+            url: url.clone(),
+            success: result.is_ok(),
+            cached,
+            bytes: result.as_ref().map_or(0, |image| image.len()),
+            duration: elapsed_time,
+        });
     }
 
-    // Getting the size of the image
-    let content_length = headers.get("content-length")
-        .unwrap()
-        .to_str()
-        .unwrap_or("0");
-    let content_length = content_length.parse::<usize>().unwrap();
+    // Saving the image
+    let image = result?;
+    client.emit(Progress::PageDownloaded {
+        url: url.clone(),
+        page_no,
+        bytes: image.len(),
+        cached,
+    });
+    match client.spool_path(&url) {
+        Some(path) => {
+            spool(&path, &image).await?;
+            Ok(MangaImage::from_file(page_no, path))
+        },
+        None => Ok(MangaImage::new(page_no, image)),
+    }
+}
 
-    // Reporting whether the image was successfully downloaded
-    let is_mangadex_url = url_clone.contains("mangadex.org");
-    if report && !res.status().is_success() && !is_mangadex_url {
-        async_report(url_clone, false, cache_state, content_length, elapsed_time)
-            .await?;
-        panic!("Failed to retrieve image!"); // TODO: Use ERR or retry instead
-    } else if report && !is_mangadex_url {
-        async_report(url_clone, true, cache_state, content_length, elapsed_time).await?;
+/// Writes an image to the spool. The image is written next to its
+/// destination first, so that an interrupted write never leaves a
+/// truncated page behind.
+async fn spool(path: &Path, image: &[u8]) -> Result<(), DexError> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
     }
+    let partial = path.with_extension("part");
+    tokio::fs::write(&partial, image).await?;
+    tokio::fs::rename(&partial, path).await?;
+    Ok(())
+}
 
-    // Saving the image
-    let image_bytes = res.bytes().await?;
-    let image = image_bytes.to_vec();
-    let image = MangaImage::new(page_no, image);
+/// Downloads and checks an image. Returns whether the server
+/// had it cached along with the image.
+async fn fetch_image(client: &DexClient, url: &str) -> (Result<Vec<u8>, DexError>, bool) {
+    let res = match send_get(client, url).await {
+        Ok(res) => res,
+        Err(e) => return (Err(e), false),
+    };
+
+    // Getting whether the server returned x-cache
+    let cached = res.headers
+        .get("x-cache")
+        .and_then(|hv| hv.to_str().ok())
+        .is_some_and(|cache| cache.starts_with("HIT"));
+
+    let result = if !res.status.is_success() {
+        Err(DexError::Http {
+            url: String::from(url),
+            status: res.status.as_u16(),
+            errors: ApiErrors::from_body(&res.body),
+        })
+    } else if client.config().verify_pages {
+        verify_image(url, res.body).await
+    } else {
+        Ok(res.body)
+    };
 
-    Ok(image)
+    (result, cached)
 }
 
-/// Reports back to the MangaDex@Home server
-/// TODO: FIX CAPTCHA. CAPTCHA reply not yet working
-async fn async_report(url: String, success: bool, cached: bool, bytes: usize, duration: u128) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let client = reqwest::Client::new();
+/// Returns the SHA-256 hash embedded in a MangaDex@Home filename
+/// such as `1-<hash>.png`, if there is one.
+/// 
+/// # Examples
+/// ```
+/// use dexloader::connection::expected_hash;
+/// 
+/// let url = "https://example.org/data/abc/1-003c7b848850c6565801d12fdb48808fbeaad9074a8694969adf67af1f8381aa.png";
+/// assert_eq!(expected_hash(url), Some("003c7b848850c6565801d12fdb48808fbeaad9074a8694969adf67af1f8381aa"));
+/// assert_eq!(expected_hash("https://example.org/covers/abc/cover.png"), None);
+/// ```
+pub fn expected_hash(url: &str) -> Option<&str> {
+    let file_name = url.rsplit('/').next()?;
+    let stem = file_name.split('.').next()?;
+    let (_, hash) = stem.split_once('-')?;
 
-    let body = ResponseBody {url, success, cached, bytes, duration};
+    let is_sha256 = hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit());
+    if is_sha256 { Some(hash) } else { None }
+}
 
-    let res = client.post("https://api.mangadex.network/report")
-        .json(&body)
-        .send()
-        .await?;
-    
-    if res.status().as_u16() == 412 {
-        let res_headers = res.headers();
-        let captcha_ans = res_headers.get("X-Captcha-Sitekey")
-            .unwrap()
-            .to_str()
-            .expect("Cannot get captcha answer");
-        
-        let cap_res = client.post("https://api.mangadex.network/report")
-            .header("X-Captcha-Result", captcha_ans)
-            .json(&body)
-            .send()
-            .await?;
+/// Checks a downloaded image against the hash in its filename, and
+/// makes sure it decodes, so that corrupted or truncated pages are
+/// caught before they end up in an epub.
+async fn verify_image(url: &str, image: Vec<u8>) -> Result<Vec<u8>, DexError> {
+    if let Some(expected) = expected_hash(url) {
+        let actual = Sha256::digest(&image)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+        if !actual.eq_ignore_ascii_case(expected) {
+            return Err(DexError::Integrity {
+                url: String::from(url),
+                reason: format!("expected SHA-256 {}, got {}", expected, actual),
+            });
+        }
     }
 
-    Ok(())
+    // Decoding is CPU bound, so it is kept off the async workers
+    task::spawn_blocking(move || image::load_from_memory(&image).map(|_| image))
+        .await
+        .map_err(|e| DexError::Io(std::io::Error::other(e)))?
+        .map_err(DexError::from)
 }
 
-/// Makes multiple get requests to fetch multiple images
-/// asynchronously.
+/// Fetches an image, retrying failed attempts with exponential
+/// backoff as dictated by the client's [`RetryPolicy`](crate::client::RetryPolicy).
 /// 
-/// The number of images to download at the same time can
-/// be specified with the "concurrent_process" variable.
-pub async fn async_get_image_batch(batch: &mut Vec<String>, concurrent_process : i32, report: bool) -> Vec<MangaImage> {
-    let mut handle_vec: Vec<JoinHandle<Result<MangaImage, Box<dyn std::error::Error + Send + Sync>>>> = Vec::new();
-    let mut result_images: Vec<MangaImage> = Vec::new();
-
-    // Iterating through all the images
-    for i in (0..batch.len()).step_by(5) {
-        // Download 5 images as a time
-        for j in 0..concurrent_process {
-            match batch.pop() {
-                Some(url) => {
-                    let page_no : i32 = i as i32 + j;
-                    handle_vec.push(task::spawn(async_get_image(url, page_no, report)));
-                },
-                None => (),
-            }
+/// The error of the last attempt is returned once the retries
+/// are exhausted.
+pub async fn async_get_image_retry(client: DexClient, url: String, page_no: i32, report: bool) -> Result<MangaImage, DexError> {
+    let policy = client.config().retry.clone();
+    let mut attempt = 0;
+
+    loop {
+        match async_get_image(client.clone(), url.clone(), page_no, report).await {
+            Ok(image) => return Ok(image),
+            Err(e @ (DexError::Offline(_) | DexError::Cancelled)) => return Err(e),
+            Err(e) if attempt < policy.max_retries => {
+                attempt += 1;
+                let delay = policy.delay(attempt);
+                warn!("Page {} failed ({}), retry {} in {:?}", page_no, e, attempt, delay);
+                client.emit(Progress::Retry {
+                    url: url.clone(),
+                    page_no,
+                    attempt,
+                    delay,
+                    error: e.to_string(),
+                });
+                tokio::select! {
+                    _ = client.cancellation().cancelled() => return Err(DexError::Cancelled),
+                    _ = tokio::time::sleep(delay) => {},
+                }
+            },
+            Err(e) => return Err(e),
         }
+    }
+}
 
-        // Getting the result
-        let mut result: Vec<MangaImage>  = join_all(handle_vec)
-            .await
-            .into_iter()
-            .map(Result::unwrap)
-            .map(Result::unwrap)
-            .collect();
+/// Makes multiple get requests to fetch multiple images
+/// asynchronously.
+/// 
+/// The batch is a list of page numbers and their URLs. Each
+/// page is returned with the result of its download, in the
+/// order of the batch, so that failed pages can be requested
+/// again.
+/// 
+/// Every page is started at once, but only as many images as
+/// the client has download slots are fetched at the same time.
+/// The slots are shared by every batch made with the client,
+/// so downloading several chapters at once does not raise the
+/// limit.
+pub async fn async_get_image_batch(client: &DexClient, batch: Vec<(i32, String)>, report: bool) -> Vec<(i32, ImageResult)> {
+    let (page_nos, handles): (Vec<i32>, Vec<JoinHandle<ImageResult>>) = batch.into_iter()
+        .map(|(page_no, url)| {
+            (page_no, task::spawn(async_get_image_retry(client.clone(), url, page_no, report)))
+        })
+        .unzip();
 
-        result_images.append(&mut result);
-        handle_vec = Vec::new();
-    }
-        
-    result_images
+    join_all(handles)
+        .await
+        .into_iter()
+        .map(|joined| joined.unwrap_or_else(|e| Err(DexError::Io(std::io::Error::other(e)))))
+        .zip(page_nos)
+        .map(|(res, page_no)| (page_no, res))
+        .collect()
 }
@@ -1,17 +1,32 @@
 //! This module concerns with how images are converted into
 //! epub
+//!
+//! epub-builder only generates the documents of the book, with the
+//! pages listed but left empty. The epub is then written in a
+//! single pass straight to its file, with every page streamed from
+//! where it is kept without being compressed again, so that spooled
+//! pages never have to be held in memory.
+//!
+//! epub-builder writes every creator as an author, so creators and
+//! contributors are kept aside and written into the package document
+//! with their roles in the same pass.
 
 extern crate epub_builder;
 
+extern crate zip;
+
+use std::collections::HashMap;
 use std::fs::{self, File};
-use crate::manga::MangaImage;
+use std::io::{self, Cursor, Read, Write};
+use crate::error::DexError;
+use crate::manga::{ImageData, MangaImage};
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+use zip::write::FileOptions;
 use epub_builder::{
     EpubBuilder,
-    Result,
     ZipLibrary,
     EpubContent,
     ReferenceType,
-    TocElement
 };
 
 static PAGE_TEMPLATE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
@@ -29,9 +44,116 @@ static PAGE_TEMPLATE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
 </body>
 </html>"#;
 
+static DETAILS_TEMPLATE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops">
+
+<head>
+    <title>TITLE</title>
+</head>
+
+<body>
+    <h1>TITLE</h1>
+    DESCRIPTION
+    <dl>
+DETAILS
+    </dl>
+</body>
+</html>"#;
+
+/// Escapes the characters that have a meaning in XHTML
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// The package document, which holds the metadata of a book
+const PACKAGE_PATH: &str = "OEBPS/content.opf";
+
+/// Returns the package document with the creators and contributors
+/// written at the end of its metadata, each with their MARC relator
+/// code
+fn write_credits(package: &str, creators: &[(String, String)], contributors: &[(String, String)]) -> Result<String, DexError> {
+    let end = package.find("</metadata>")
+        .ok_or_else(|| DexError::Epub(String::from("the package document has no metadata to add the creators to")))?;
+    let entry = |element: &str, (name, role): &(String, String)| {
+        format!("  <dc:{0} opf:role=\"{1}\">{2}</dc:{0}>\n  ", element, escape(role), escape(name))
+    };
+    let entries: String = creators.iter()
+        .map(|creator| entry("creator", creator))
+        .chain(contributors.iter().map(|contributor| entry("contributor", contributor)))
+        .collect();
+    Ok(format!("{}{}{}", &package[..end], entries, &package[end..]))
+}
+
+/// Where the cover is written in the epub
+const COVER_PATH: &str = "Images/cover.jpg";
+
+/// Writes the epub to `to`, copying the documents generated by
+/// epub-builder and streaming the `cover` and `pages` into the
+/// empty files left for them, keyed by their path in the archive.
+/// The creators and contributors are written into the package
+/// document, each with their MARC relator code.
+/// 
+/// Images are stored as they are, as they are compressed already.
+#[allow(clippy::too_many_arguments)]
+fn write_archive<F>(
+    documents: Vec<u8>,
+    to: &str,
+    cover: Option<&ImageData>,
+    pages: &HashMap<String, &ImageData>,
+    creators: &[(String, String)],
+    contributors: &[(String, String)],
+    mut on_page: F,
+) -> Result<(), DexError>
+    where F: FnMut(usize, usize)
+{
+    let zip_error = |e: zip::result::ZipError| DexError::Epub(e.to_string());
+    let mut archive = ZipArchive::new(Cursor::new(documents)).map_err(zip_error)?;
+    let mut writer = ZipWriter::new(File::create(to)?);
+    let cover_path = format!("OEBPS/{}", COVER_PATH);
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+
+    let mut written = 0;
+    for i in 0..archive.len() {
+        let file = archive.by_index_raw(i).map_err(zip_error)?;
+        let name = String::from(file.name());
+        if let Some(image) = cover.filter(|_| name == cover_path) {
+            drop(file);
+            writer.start_file(name, stored).map_err(zip_error)?;
+            io::copy(&mut image.reader()?, &mut writer)?;
+        } else if let Some(page) = pages.get(&name) {
+            drop(file);
+            writer.start_file(name, stored).map_err(zip_error)?;
+            io::copy(&mut page.reader()?, &mut writer)?;
+            written += 1;
+            on_page(written, pages.len());
+        } else if name == PACKAGE_PATH {
+            drop(file);
+            let mut package = String::new();
+            archive.by_index(i).map_err(zip_error)?.read_to_string(&mut package)?;
+            let package = write_credits(&package, creators, contributors)?;
+
+            writer.start_file(PACKAGE_PATH, FileOptions::default()).map_err(zip_error)?;
+            writer.write_all(package.as_bytes())?;
+        } else {
+            writer.raw_copy_file(file).map_err(zip_error)?;
+        }
+    }
+    writer.finish().map_err(zip_error)?;
+    Ok(())
+}
+
 pub struct Book {
     pub constructor: EpubBuilder<ZipLibrary>,
     pub resources: Vec<BookContent>,
+    pub cover: Option<ImageData>,
+    /// The creators and their MARC relator codes
+    pub creators: Vec<(String, String)>,
+    /// The contributors, such as translators, and their MARC
+    /// relator codes
+    pub contributors: Vec<(String, String)>,
 }
 
 pub struct BookContent {
@@ -44,35 +166,97 @@ pub struct BookContent {
 }
 
 pub enum Content {
-    Image(Vec<u8>),
+    Image(ImageData),
     Text(String),
 }
 
 impl Book {
-    pub fn new() -> Self {
-        let constructor = EpubBuilder::new(ZipLibrary::new().unwrap())
-            .unwrap();
-        Book { constructor, resources: Vec::<BookContent>::new() }
+    pub fn new() -> Result<Self, DexError> {
+        let constructor = EpubBuilder::new(ZipLibrary::new()?)?;
+        Ok(Book { constructor, resources: Vec::<BookContent>::new(), cover: None, creators: Vec::new(), contributors: Vec::new() })
+    }
+
+    pub fn add_author(&mut self, name: &str) -> Result<(), DexError> {
+        self.add_creator(name, "aut")
+    }
+
+    /// Adds a creator with a MARC relator code for their role, such
+    /// as `aut` for an author or `art` for an artist. Empty names
+    /// are left out.
+    pub fn add_creator(&mut self, name: &str, role: &str) -> Result<(), DexError> {
+        if !name.is_empty() {
+            self.creators.push((String::from(name), String::from(role)));
+        }
+        Ok(())
+    }
+
+    /// Adds a contributor with a MARC relator code for their role,
+    /// such as `trl` for a translator. Empty names are left out.
+    pub fn add_contributor(&mut self, name: &str, role: &str) -> Result<(), DexError> {
+        if !name.is_empty() {
+            self.contributors.push((String::from(name), String::from(role)));
+        }
+        Ok(())
+    }
+
+    // epub-builder writes metadata as it is, so it is escaped here
+    pub fn add_title(&mut self, title: &str) -> Result<(), DexError> {
+        self.constructor.metadata("title", escape(title))?;
+        Ok(())
+    }
+
+    pub fn add_description(&mut self, description: &str) -> Result<(), DexError> {
+        self.constructor.metadata("description", escape(description))?;
+        Ok(())
+    }
+
+    pub fn add_subject(&mut self, subject: &str) -> Result<(), DexError> {
+        self.constructor.metadata("subject", escape(subject))?;
+        Ok(())
     }
 
-    pub fn add_author(&mut self, name: &str) -> Result<()> {
-        self.constructor.metadata("author", name)?;
+    /// Sets the language of the book, written as its `dc:language`
+    pub fn add_language(&mut self, language: &str) -> Result<(), DexError> {
+        self.constructor.metadata("lang", escape(language))?;
         Ok(())
     }
 
-    pub fn add_title(&mut self, title: &str) -> Result<()> {
-        self.constructor.metadata("title", title)?;
+    /// Adds a page with the title, the description and labelled
+    /// details of the manga, such as its status and tags.
+    pub fn add_details_page(&mut self, title: &str, description: Option<&str>, details: &[(String, String)]) -> Result<(), DexError> {
+        let description = description
+            .map(|description| {
+                let paragraphs: Vec<String> = description.split("\n")
+                    .map(str::trim)
+                    .filter(|paragraph| !paragraph.is_empty())
+                    .map(|paragraph| format!("<p>{}</p>", escape(paragraph)))
+                    .collect();
+                paragraphs.join("\n    ")
+            })
+            .unwrap_or_default();
+        let details: Vec<String> = details.iter()
+            .map(|(label, value)| format!("        <dt>{}</dt><dd>{}</dd>", escape(label), escape(value)))
+            .collect();
+
+        let page_html = DETAILS_TEMPLATE
+            .replace("TITLE", &escape(title))
+            .replace("DESCRIPTION", &description)
+            .replace("DETAILS", &details.join("\n"));
+        let content = EpubContent::new("Text/details.xhtml", page_html.as_bytes())
+            .title("Details")
+            .reftype(ReferenceType::TitlePage);
+        self.constructor.add_content(content)?;
         Ok(())
     }
 
-    pub fn add_css(&mut self, path: &str, target: &str) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    pub fn add_css(&mut self, path: &str, target: &str) -> Result<(), DexError> {
         let css = fs::read(path)?;
         self.constructor.add_resource(target, css.as_slice(), "text/css")?;
         Ok(())
     }
 
     pub fn add_image(&mut self, img: &mut MangaImage, order: i32, target_path: &str, chapter_order: i32) {
-        let img_free = std::mem::replace(&mut img.image, Vec::new());
+        let img_free = img.take();
         let resource = BookContent {
             order,
             chapter_order,
@@ -94,21 +278,38 @@ impl Book {
         self.resources.push(resource);
     }
 
-    pub fn add_cover_image(&mut self, img: &mut MangaImage) -> Result<()> {
-        let image = std::mem::replace(&mut img.image, Vec::new());
-        self.constructor.add_cover_image("Images/cover.jpg", image.as_slice(), "image/jpeg")?;
+    pub fn add_cover_image(&mut self, img: &mut MangaImage) -> Result<(), DexError> {
+        self.constructor.add_cover_image(COVER_PATH, io::empty(), "image/jpeg")?;
+        self.cover = Some(img.take());
         Ok(())
     }
 
-    pub fn generate(&mut self, output_path: &str) -> Result<()> {
-        // TODO: USE output_path
+    pub fn generate(&mut self, output_path: &str) -> Result<(), DexError> {
+        self.generate_with_progress(output_path, |_, _| {})
+    }
+
+    /// Returns the number of pages added to the book
+    pub fn page_count(&self) -> usize {
+        self.resources.iter()
+            .filter(|resource| matches!(resource.content, Content::Image(_)))
+            .count()
+    }
+
+    /// Writes the book like [`generate`](Book::generate), calling
+    /// `on_page` with the pages written so far and the page count
+    /// after each page.
+    pub fn generate_with_progress<F>(&mut self, output_path: &str, mut on_page: F) -> Result<(), DexError>
+        where F: FnMut(usize, usize)
+    {
         self.constructor.inline_toc();
-        
+
+        let mut pages: HashMap<String, &ImageData> = HashMap::new();
         for resource in &self.resources {
             match &resource.content {
                 Content::Image(data) => {
-                    self.constructor.add_resource(&resource.target_path, data.as_slice(), "image/jpeg")?;
-                    
+                    self.constructor.add_resource(&resource.target_path, io::empty(), "image/jpeg")?;
+                    pages.insert(format!("OEBPS/{}", &resource.target_path), data);
+
                     let page_html = PAGE_TEMPLATE.replace(
                         "IMAGE_SOURCE",
                         &format!("../{}", &resource.target_path)
@@ -130,11 +331,17 @@ impl Book {
             }
         }
 
-        let f = File::create(output_path)
-            .expect(&format!("Failed to create file: {}", output_path));
-        //self.constructor.generate(&mut std::io::stdout())?;
-        self.constructor.generate(f)?;
-        
-        Ok(())
+        let mut documents = Vec::new();
+        self.constructor.generate(&mut documents)?;
+
+        // Writing next to the output first, so that it only appears
+        // once complete
+        let partial = format!("{}.part", output_path);
+        let result = write_archive(documents, &partial, self.cover.as_ref(), &pages, &self.creators, &self.contributors, &mut on_page)
+            .and_then(|_| fs::rename(&partial, output_path).map_err(DexError::from));
+        if result.is_err() {
+            let _ = fs::remove_file(&partial);
+        }
+        result
     }
 }
\ No newline at end of file
@@ -0,0 +1,124 @@
+//! This module contains the error type returned by every
+//! fallible function of the crate.
+extern crate thiserror;
+
+use serde::{Serialize, Deserialize};
+use thiserror::Error;
+
+use std::fmt;
+use std::time::Duration;
+
+/// A single error reported by the MangaDex API.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ApiError {
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub status: u16,
+    #[serde(default)]
+    pub title: String,
+    #[serde(default)]
+    pub detail: Option<String>,
+}
+
+/// The body of a failed MangaDex API response.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct ErrorResponse {
+    pub errors: Vec<ApiError>,
+}
+
+/// The errors reported by the MangaDex API in a response,
+/// displayed as `title: detail` separated by semicolons.
+#[derive(Clone, Debug, Default)]
+pub struct ApiErrors(pub Vec<ApiError>);
+
+impl ApiErrors {
+    /// Parses the errors out of a response body, if it contains any
+    pub fn from_body(body: &[u8]) -> Self {
+        let errors = serde_json::from_slice::<ErrorResponse>(body)
+            .map(|res| res.errors)
+            .unwrap_or_default();
+        ApiErrors(errors)
+    }
+}
+
+impl fmt::Display for ApiErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let messages: Vec<String> = self.0.iter()
+            .map(|e| match &e.detail {
+                Some(detail) => format!("{}: {}", e.title, detail),
+                None => e.title.clone(),
+            })
+            .collect();
+        write!(f, "{}", messages.join("; "))
+    }
+}
+
+/// Everything that can go wrong while fetching a manga and
+/// turning it into an epub.
+#[derive(Debug, Error)]
+pub enum DexError {
+    /// The request could not be sent or its response not read
+    #[error("network error: {0}")]
+    Network(#[from] reqwest::Error),
+
+    /// The server answered with a non-success status
+    #[error("{url} returned {status}: {errors}")]
+    Http { url: String, status: u16, errors: ApiErrors },
+
+    /// The response body did not match the expected format
+    #[error("failed to decode the response of {url}: {source}")]
+    Json { url: String, source: serde_json::Error },
+
+    /// The requested resource does not exist
+    #[error("not found: {0}")]
+    NotFound(String),
+
+    /// The API kept answering with 429
+    #[error("rate limited on {url}")]
+    RateLimited { url: String, retry_after: Option<Duration> },
+
+    /// A downloaded page does not match the hash in its filename,
+    /// or the server of a chapter lists a different number of pages
+    /// than the chapter has
+    #[error("{url} failed verification: {reason}")]
+    Integrity { url: String, reason: String },
+
+    /// The downloaded bytes are not a valid image
+    #[error("failed to decode image: {0}")]
+    ImageDecode(#[from] image::ImageError),
+
+    /// The epub could not be assembled or written
+    #[error("failed to build epub: {0}")]
+    Epub(String),
+
+    /// Reading or writing a local file failed
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// The resource is not cached and the client is offline
+    #[error("{0} is not available offline")]
+    Offline(String),
+
+    /// A replayed request is missing from the cassette
+    #[error("no recorded response for {0}")]
+    NotRecorded(String),
+
+    /// The cassette could not be read or written
+    #[error("invalid cassette: {0}")]
+    Cassette(String),
+
+    /// The work was stopped through the client's cancellation token
+    #[error("cancelled")]
+    Cancelled,
+
+    /// The client settings are invalid
+    #[error("invalid client configuration: {0}")]
+    Config(String),
+}
+
+impl From<epub_builder::Error> for DexError {
+    fn from(e: epub_builder::Error) -> Self {
+        DexError::Epub(e.to_string())
+    }
+}
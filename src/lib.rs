@@ -7,21 +7,29 @@
 //! format as well.
 //! 
 //! # Example
-//! ```
+//! ```no_run
 //! use dexloader::manga::Manga;
 //! 
 //! #[tokio::main]
 //! async fn main() -> Result<(), Box<dyn std::error::Error>> {
 //!     let url = "https://mangadex.org/title/259dfd8a-f06a-4825-8fa6-a2dcd7274230/yofukashi-no-uta";
-//!     let manga = Manga::from(url);
-//!     let chapters = manga.get_chapters(None, 0).await;
-//!     let first_chapter = &chapters.data[0];
+//!     let mut manga = Manga::from(url);
+//!     manga.get_chapters(None, 0, None).await?;
+//!     let first_chapter = &manga.chapter_list.as_ref().unwrap().data[0];
 //! 
-//!     first_chapter.download().await;
+//!     first_chapter.download(manga.get_client(), true).await?;
+//!     Ok(())
 //! }
 //! ```
 
+pub mod cache;
+pub mod cassette;
+pub mod client;
+pub mod error;
 pub mod connection;
+pub mod rate_limit;
+pub mod report;
+pub mod progress;
 pub mod manga;
 pub mod util;
 pub mod epub;
\ No newline at end of file
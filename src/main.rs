@@ -1,110 +1,255 @@
+mod progress_bars;
+
+use std::error::Error;
+use std::fmt::Display;
 use std::str::FromStr;
 use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use dexloader::cassette::CassetteMode;
+use dexloader::client::{ClientConfig, DexClient};
+use dexloader::error::DexError;
+use dexloader::manga::{LanguageMode, Manga, Quality, SelectionOptions};
+use dexloader::util::sanitize_file_name;
+
+use progress_bars::ProgressBars;
 
-use dexloader::manga::Manga;
+/// Returns the value following a flag, parsed into the wanted type
+fn flag_value<'a, T, I>(arguments: &mut I, flag: &str) -> Result<T, Box<dyn Error>>
+    where
+        T: FromStr,
+        T::Err: Display,
+        I: Iterator<Item = &'a String>,
+{
+    let value = arguments.next()
+        .ok_or_else(|| format!("No value specified for {}", flag))?;
+    T::from_str(value.trim())
+        .map_err(|e| format!("Failed to parse {} value \"{}\": {}", flag, value, e).into())
+}
+
+/// Returns the directory pages are cached in by default, following
+/// `XDG_CACHE_HOME` and falling back to `~/.cache`
+fn default_cache_dir() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))?;
+    Some(base.join("dexloader"))
+}
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() -> Result<(), Box<dyn Error>> {
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+
     // Dealing with command line arguments
     let args: Vec<String> = env::args().collect();
-    let url = &args[1];
+    let url = args.get(1)
+        .ok_or("Usage: dexloader <manga url> [options]")?;
 
     let mut argument_iterator = args.iter();
     argument_iterator.next();
     argument_iterator.next();
 
-    let mut output_path: &str = ".epub";
-    let mut limit: Option<i32> = Some(6);
+    let mut output_path: Option<String> = None;
+    let mut limit: i32 = 6;
     let mut start = 0;
     let mut end: Option<i32> = None;
     let mut single = false;
+    let mut by_volume = false;
     let mut report = true;
-    
+    let mut config = ClientConfig::default();
+    let mut selection = SelectionOptions::default();
+    let mut cache_dir = default_cache_dir();
+
     while let Some(val) = argument_iterator.next() {
-        if val == "-o" || val == "--output" {
-            match argument_iterator.next() {
-                Some(path) => output_path = path.trim(),
-                None => panic!("No output path specified")
-            };
-        } else if val == "-l" || val == "--limit" {
-            match argument_iterator.next() {
-                Some(l) => {
-                    let lim = i32::from_str(l)
-                        .expect("Failed to parse limit value");
-                    limit = Some(lim);
-                },
-                None => panic!("No limit value specified")
-            }
-        } else if val == "-s" || val == "--start" {
-            match argument_iterator.next() {
-                Some(s) => {
-                    let st = i32::from_str(s)
-                        .expect("Failed to parse start value");
-                    start = st;
-                },
-                None => panic!("No start value specified")
-            }
-        } else if val == "-e" || val == "--end" {
-            match argument_iterator.next() {
-                Some(e) => {
-                    let ed = i32::from_str(e)
-                        .expect("Failed to parse end value");
-                    end = Some(ed);
-                },
-                None => panic!("No end value specified")
-            }
-        } else if val == "--single" {
-            single = true;
-        } else if val == "--no-report" {
-            report = false;
+        let flag = val.as_str();
+        match flag {
+            "-o" | "--output" => output_path = Some(flag_value(&mut argument_iterator, flag)?),
+            "-l" | "--limit" => limit = flag_value(&mut argument_iterator, flag)?,
+            "-s" | "--start" => start = flag_value(&mut argument_iterator, flag)?,
+            "-e" | "--end" => end = Some(flag_value(&mut argument_iterator, flag)?),
+            "--single" => single = true,
+            "--by-volume" => by_volume = true,
+            "--no-report" => report = false,
+            "--no-verify" => config.verify_pages = false,
+            "-q" | "--quality" => config.quality = flag_value(&mut argument_iterator, flag)?,
+            "--data-saver" => config.quality = Quality::DataSaver,
+            "--proxy" => config.proxy = Some(flag_value(&mut argument_iterator, flag)?),
+            "--user-agent" => config.user_agent = flag_value(&mut argument_iterator, flag)?,
+            "--timeout" => {
+                let secs = flag_value(&mut argument_iterator, flag)?;
+                config.read_timeout = Some(Duration::from_secs(secs));
+            },
+            "-j" | "--concurrency" => config.concurrent_downloads = flag_value(&mut argument_iterator, flag)?,
+            "--retries" => config.retry.max_retries = flag_value(&mut argument_iterator, flag)?,
+            "--refreshes" => config.retry.max_refreshes = flag_value(&mut argument_iterator, flag)?,
+            "--retry-delay" => {
+                let millis = flag_value(&mut argument_iterator, flag)?;
+                config.retry.base_delay = Duration::from_millis(millis);
+            },
+            "--spool" => config.spool_dir = Some(flag_value(&mut argument_iterator, flag)?),
+            "--cache" => cache_dir = Some(flag_value(&mut argument_iterator, flag)?),
+            "--no-cache" => cache_dir = None,
+            "--offline" => config.offline = true,
+            "--record" => config.cassette = Some(CassetteMode::Record(flag_value(&mut argument_iterator, flag)?)),
+            "--replay" => config.cassette = Some(CassetteMode::Replay(flag_value(&mut argument_iterator, flag)?)),
+            "--title-language" => {
+                let languages: String = flag_value(&mut argument_iterator, flag)?;
+                selection.title_languages = languages.split(',')
+                    .map(str::trim)
+                    .filter(|language| !language.is_empty())
+                    .map(String::from)
+                    .collect();
+            },
+            "--language" => {
+                let languages: String = flag_value(&mut argument_iterator, flag)?;
+                selection.languages = languages.split(',')
+                    .map(str::trim)
+                    .filter(|language| !language.is_empty())
+                    .map(String::from)
+                    .collect();
+                selection.language_mode = LanguageMode::Preferred;
+            },
+            "--original-language" => selection.language_mode = LanguageMode::Original,
+            "--all-languages" => selection.language_mode = LanguageMode::All,
+            "--prefer-group" => selection.preferred_groups.push(flag_value(&mut argument_iterator, flag)?),
+            "--unnumbered" => selection.unnumbered = flag_value(&mut argument_iterator, flag)?,
+            "--duplicates" => selection.duplicates = flag_value(&mut argument_iterator, flag)?,
+            "--block-group" => selection.blocked_groups.push(flag_value(&mut argument_iterator, flag)?),
+            _ => return Err(format!("Unknown option: {}", flag).into()),
         }
     }
 
-    // Driving the program
-    //let mut url = String::new();
-
-    //io::stdin()
-    //    .read_line(&mut url)
-    //    .expect("Failed to read line");
-    let url = url.trim();
-    
-    let mut manga = Manga::from(url);
-    let mut total = 0;
-    loop {
-        if total != 0 && start > total {
-            if single {
-                manga.generate_epub(output_path).await;
-            }
-            break;
-        }
+    // Pages go to the cache, or are spooled to a temporary
+    // directory unless one is given
+    // Sessions are recorded and replayed in full, without the cache
+    // answering some of the requests
+    if config.cassette.is_some() {
+        cache_dir = None;
+    }
+    if config.offline && cache_dir.is_none() {
+        return Err("--offline needs a cache to read from".into());
+    }
+    config.cache_dir = cache_dir;
+    let temporary_spool = match (&config.spool_dir, &config.cache_dir) {
+        (Some(_), _) | (None, Some(_)) => None,
+        (None, None) => {
+            let dir = env::temp_dir().join(format!("dexloader-{}", std::process::id()));
+            config.spool_dir = Some(dir.clone());
+            Some(dir)
+        },
+    };
 
-        match end {
-            Some(i) => {
-                if i <= start {
-                    break;
-                }
-            },
-            None => (),
+    let resumable = config.cache_dir.is_some();
+    let bars = Arc::new(ProgressBars::new());
+    let listener = bars.clone();
+    let client = DexClient::new(config)?
+        .with_progress(move |event| listener.handle(event));
+
+    // The first Ctrl-C lets the pages being downloaded finish, the
+    // second one quits at once
+    let cancellation = client.cancellation().clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            eprintln!("Stopping after the pages in progress, press Ctrl-C again to quit now");
+            cancellation.cancel();
+        }
+        if tokio::signal::ctrl_c().await.is_ok() {
+            std::process::exit(130);
         }
-        
-        manga.get_chapters(limit, start, end).await;
-        total = manga.get_total().unwrap();
-
-        manga.download_chapters(!single, report).await
-            .expect("Failed to download chapters");
-        
-        if !single {
-            let temp = format!("File_{}_", start/limit.unwrap());
-            let path = temp + output_path;
-            println!("Saving {}", path);
-            manga.generate_epub(&path).await;
+    });
+
+    let result = if by_volume {
+        run_by_volume(url.trim(), client.clone(), selection, output_path, report).await
+    } else {
+        run(url.trim(), client.clone(), selection, output_path, limit, start, end, single, report).await
+    };
+    client.flush_reports().await;
+    bars.finish();
+
+    if let Some(dir) = temporary_spool {
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    let cancelled = result.as_ref()
+        .err()
+        .and_then(|e| e.downcast_ref::<DexError>())
+        .is_some_and(|e| matches!(e, DexError::Cancelled));
+    if cancelled {
+        if resumable {
+            eprintln!("Stopped. Run the same command again to continue where it stopped.");
         } else {
-            println!("Saving {}", output_path);
-            manga.generate_epub(&output_path).await;
-            break;
+            eprintln!("Stopped.");
         }
+        std::process::exit(130);
+    }
+    result
+}
+
+/// Returns the path given with `--output`, or the title of the
+/// manga as a filename
+fn epub_path(manga: &Manga, output_path: &Option<String>) -> String {
+    match output_path {
+        Some(path) => path.clone(),
+        None => format!("{}.epub", sanitize_file_name(&manga.title)),
+    }
+}
+
+/// Returns `path` with `prefix` put in front of its file name, so
+/// that the epub stays in the directory given with `--output`
+fn prefixed_path(path: &str, prefix: &str) -> String {
+    let path = Path::new(path);
+    let file_name = path.file_name().map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+    let prefixed = format!("{}_{}", prefix, file_name);
+    match path.parent() {
+        Some(parent) => parent.join(prefixed).to_string_lossy().into_owned(),
+        None => prefixed,
+    }
+}
+
+/// Downloads every chapter and saves one epub per volume, with
+/// the chapters not assigned to a volume in an epub of their own
+async fn run_by_volume(url: &str, client: DexClient, selection: SelectionOptions, output_path: Option<String>, report: bool) -> Result<(), Box<dyn Error>> {
+    let mut manga = Manga::with_client(url, client).with_selection(selection);
+    manga.get_all_chapters().await?;
+
+    for volume in manga.get_volumes() {
+        manga.download_volume(&volume, true, report).await?;
+        let path = prefixed_path(&epub_path(&manga, &output_path), &volume.to_file_name());
+        manga.generate_volume_epub(&volume, &path).await?;
+    }
+
+    Ok(())
+}
+
+/// Downloads the chapters from `start` up to `end` and saves them as
+/// epubs of `limit` chapters each, or only the first epub with
+/// `single`
+#[allow(clippy::too_many_arguments)]
+async fn run(url: &str, client: DexClient, selection: SelectionOptions, output_path: Option<String>, limit: i32, start: i32, end: Option<i32>, single: bool, report: bool) -> Result<(), Box<dyn Error>> {
+    if limit <= 0 {
+        return Err("--limit must be at least 1".into());
+    }
+    let limit = limit as usize;
+
+    let mut manga = Manga::with_client(url, client).with_selection(selection);
+    manga.get_all_chapters().await?;
+
+    let count = manga.chapter_list.as_ref().map_or(0, |list| list.data.len());
+    let start = (start.max(0) as usize).min(count);
+    let end = end.map_or(count, |end| (end.max(0) as usize).min(count)).max(start);
+
+    if single {
+        manga.download_range(start..end.min(start + limit), true, report).await?;
+        manga.generate_epub(&epub_path(&manga, &output_path)).await?;
+        return Ok(());
+    }
 
-        start += limit.unwrap();
+    for first in (start..end).step_by(limit) {
+        manga.download_range(first..end.min(first + limit), true, report).await?;
+        let path = prefixed_path(&epub_path(&manga, &output_path), &format!("File_{}", first/limit));
+        manga.generate_epub(&path).await?;
     }
 
     Ok(())
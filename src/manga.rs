@@ -1,3 +1,4 @@
+#[allow(clippy::module_inception)]
 pub mod manga;
 pub use manga::Manga;
 
@@ -7,8 +8,20 @@ pub use author::AuthorData;
 pub mod cover;
 pub use cover::CoverData;
 
+pub mod creator;
+pub use creator::{Creator, CreatorRole};
+
+pub mod group;
+pub use group::ScanlationGroup;
+
+pub mod relationship;
+pub use relationship::Relationship;
+
 pub mod manga_data;
 pub use manga_data::MangaData;
 
+pub mod selection;
+pub use selection::SelectionOptions;
+
 pub mod chapter;
-pub use chapter::{Chapter, MangaImage, ChapterImage, ChapterList, AtHomeServerResponse};
\ No newline at end of file
+pub use chapter::{Chapter, MangaImage, ChapterImage, ImageData, ChapterList, ChapterFeed, FeedOrder, SortDirection, ChapterNumber, UnnumberedPlacement, Volume, DuplicateStrategy, LanguageMode, AtHomeServerResponse, Quality};
\ No newline at end of file
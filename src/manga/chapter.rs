@@ -1,9 +1,16 @@
+#[allow(clippy::module_inception)]
 pub mod chapter;
 pub mod at_home;
 pub mod chapter_list;
+pub mod chapter_feed;
+pub mod chapter_number;
+pub mod volume;
 pub mod manga_image;
 
 pub use chapter::Chapter;
-pub use manga_image::{MangaImage, ChapterImage};
-pub use chapter_list::ChapterList;
-pub use at_home::AtHomeServerResponse;
\ No newline at end of file
+pub use manga_image::{MangaImage, ChapterImage, ImageData};
+pub use chapter_list::{ChapterList, DuplicateStrategy, LanguageMode};
+pub use chapter_feed::{ChapterFeed, FeedOrder, SortDirection};
+pub use chapter_number::{ChapterNumber, UnnumberedPlacement};
+pub use volume::Volume;
+pub use at_home::{AtHomeServerResponse, Quality};
\ No newline at end of file
@@ -1,5 +1,35 @@
 use serde::{Serialize, Deserialize};
 
+use std::str::FromStr;
+
+/// The quality pages are downloaded in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Quality {
+    /// The original images, from `/data/`
+    #[default]
+    Original,
+    /// Compressed, smaller images, from `/data-saver/`
+    DataSaver,
+    /// The original images, falling back to the compressed
+    /// version of the pages that cannot be downloaded
+    OriginalWithFallback,
+}
+
+impl FromStr for Quality {
+    type Err = String;
+
+    /// Parses `original`, `data-saver` or `fallback`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "original" => Ok(Quality::Original),
+            "data-saver" => Ok(Quality::DataSaver),
+            "fallback" => Ok(Quality::OriginalWithFallback),
+            _ => Err(format!("unknown quality {}, expected original, data-saver or fallback", s)),
+        }
+    }
+}
+
 /// Contains the response from MangaDex's @Home server.
 /// 
 /// This information is required to construct a URL to
@@ -51,4 +81,35 @@ impl AtHomeServerResponse {
     pub fn get_mut_data_save(&mut self) -> &mut Vec<String> {
         self.chapter_data.data_saver.as_mut()
     }
+
+    /// Returns the number of pages listed for the given quality
+    pub fn page_count(&self, data_saver: bool) -> usize {
+        if data_saver {
+            self.get_data_save().len()
+        } else {
+            self.get_data().len()
+        }
+    }
+
+    /// Returns the filename of a page, either in its original
+    /// quality or from the data saver, or `None` if there is no
+    /// such page.
+    pub fn page_file(&self, page_no: usize, data_saver: bool) -> Option<&str> {
+        let files = if data_saver {
+            self.get_data_save()
+        } else {
+            self.get_data()
+        };
+        files.get(page_no).map(String::as_str)
+    }
+
+    /// Returns the URL of a page, either in its original quality
+    /// or from the data saver, or `None` if there is no such page.
+    pub fn page_url(&self, page_no: usize, data_saver: bool) -> Option<String> {
+        let directory = if data_saver { "data-saver" } else { "data" };
+
+        self.page_file(page_no, data_saver).map(|file| {
+            format!("{}/{}/{}/{}", &self.base_url, directory, self.get_hash(), file)
+        })
+    }
 }
@@ -1,46 +1,81 @@
-extern crate async_trait;
-
 use serde::{Serialize, Deserialize};
-use async_trait::async_trait;
+use log::{debug, warn};
+
+use std::collections::BTreeMap;
 
 use crate::util;
+use crate::client::DexClient;
+use crate::error::DexError;
 use crate::connection::{self, AsyncGet};
+use crate::manga::{Relationship, ScanlationGroup};
+use crate::progress::Progress;
 
-use super::at_home::AtHomeServerResponse;
+use super::at_home::{AtHomeServerResponse, Quality};
+use super::chapter_number::ChapterNumber;
+use super::volume::Volume;
 use super::manga_image::MangaImage;
 
 /// A wrapper for the id of the manga chapter and its attributes
 #[derive(Serialize, Deserialize)]
 pub struct Chapter {
     pub id: String,
-    attributes: ChapterAttribute
+    attributes: ChapterAttribute,
+    #[serde(default)]
+    relationships: Vec<Relationship>,
 }
 
-/// The attributes of a manga. Contains the chapter number, 
-/// the number of pages, and the title of the chapter.
+/// The attributes of a manga. Contains the volume, the chapter
+/// number, the number of pages, and the title of the chapter.
 #[derive(Serialize, Deserialize)]
 struct ChapterAttribute {
-    //#[serde(rename = "chapter")]
-    #[serde(deserialize_with = "util::deserialize_to_option_f32")]
-    chapter: Option<f32>,
+    #[serde(default)]
+    volume: Volume,
+    #[serde(default)]
+    chapter: ChapterNumber,
     pages: i32,
 
     #[serde(deserialize_with = "util::deserialize_title")]
-    title: String
-}
+    title: String,
 
-#[async_trait]
-impl AsyncGet for Chapter {}
+    #[serde(default, rename = "translatedLanguage")]
+    translated_language: Option<String>,
+
+    #[serde(default, rename = "createdAt")]
+    created_at: Option<String>,
+}
 
 impl Chapter {
     /// Returns the chapter number
-    pub fn get_chapter_number(&self) -> f32 {
-        self.attributes.chapter.unwrap_or(0.0)
+    pub fn get_chapter_number(&self) -> &ChapterNumber {
+        &self.attributes.chapter
+    }
+
+    /// Returns the volume of the chapter
+    pub fn get_volume(&self) -> &Volume {
+        &self.attributes.volume
+    }
+
+    /// Returns the language the chapter was translated into
+    pub fn get_language(&self) -> Option<&str> {
+        self.attributes.translated_language.as_deref()
     }
 
-    /// Returns the volume and chapter number to be used as file name
+    /// Returns when the chapter was uploaded, as an ISO 8601 date
+    pub fn get_upload_date(&self) -> Option<&str> {
+        self.attributes.created_at.as_deref()
+    }
+
+    /// Returns the volume and chapter number to be used as file name.
+    /// Unnumbered chapters are told apart by their id, as a volume
+    /// can hold several of them.
     pub fn generate_file_name(&self) -> String {
-        String::from(format!("Images/{}_ORDER.jpg", self.attributes.chapter.unwrap_or(0.0)))
+        let chapter = &self.attributes.chapter;
+        let chapter = if chapter.is_numbered() {
+            chapter.to_file_name()
+        } else {
+            format!("{}_{}", chapter.to_file_name(), &self.id)
+        };
+        format!("Images/{}_{}_ORDER.jpg", self.attributes.volume.to_file_name(), chapter)
     }
 
     /// Returns the number of pages of a chapter
@@ -53,52 +88,210 @@ impl Chapter {
         self.attributes.title.as_ref()
     }
 
+    /// Returns the references of the chapter, such as its
+    /// scanlation groups and uploader
+    pub fn get_relationships(&self) -> &[Relationship] {
+        &self.relationships
+    }
+
+    /// Returns the scanlation groups that translated the chapter
+    pub fn get_groups(&self) -> Vec<ScanlationGroup> {
+        self.relationships.iter()
+            .filter(|relation| relation.relation_type == "scanlation_group")
+            .map(|relation| ScanlationGroup {
+                id: relation.id.clone(),
+                name: String::from(relation.get_name().unwrap_or(&relation.id)),
+            })
+            .collect()
+    }
+
+    /// Returns the ids of the scanlation groups of the chapter
+    pub fn get_group_ids(&self) -> Vec<&str> {
+        self.relationships.iter()
+            .filter(|relation| relation.relation_type == "scanlation_group")
+            .map(|relation| relation.id.as_str())
+            .collect()
+    }
+
     /// Sends a request to the mangadex@home server asynchronously
-    async fn get_manga_at_home_info(&self) -> AtHomeServerResponse {
-        let mut url = String::from("https://api.mangadex.org/at-home/server/");
-        url.push_str(&self.id);
+    async fn get_manga_at_home_info(&self, client: &DexClient) -> Result<AtHomeServerResponse, DexError> {
+        let url = client.api_url(&format!("/at-home/server/{}", &self.id));
 
-        let body = self.async_get_json::<AtHomeServerResponse>(&url).await;
-        body
+        client.async_get_json::<AtHomeServerResponse>(&url).await
     }
 
     /// Downloads the images of a chapter of manga
     /// 
     /// # Examples
-    /// ```
+    /// ```no_run
     /// use dexloader::manga::Manga;
     /// 
     /// #[tokio::main]
     /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
     ///     let url = "https://mangadex.org/title/efb4278c-a761-406b-9d69-19603c5e4c8b/the-100-girlfriends-who-really-really-really-really-really-love-you";
-    ///     let manga = Manga::from(url);
-    ///     let chapters = manga.get_chapters(None, 0).await;
-    ///     let first_chapter = &chapters.data[0];
+    ///     let mut manga = Manga::from(url);
+    ///     manga.get_chapters(None, 0, None).await?;
+    ///     let first_chapter = &manga.chapter_list.as_ref().unwrap().data[0];
     /// 
-    ///     first_chapter.download().await;
+    ///     first_chapter.download(manga.get_client(), true).await?;
     ///     Ok(())
     /// }
     /// ```
-    pub async fn download(&self, report: bool) -> Vec<MangaImage> {
-        let mut at_home_info = self.get_manga_at_home_info().await;
-        let mut url = String::from(&at_home_info.base_url);
-        url.push_str("/data/");
-        url.push_str(at_home_info.get_hash());
-        url.push_str("/");
-
-        let all_img = at_home_info.get_mut_data();
-        let mut all_img_url: Vec<String> = all_img.iter()
-            .map(|img_name| -> String {
-                let mut u = String::from(&url);
-                u.push_str(img_name);
-                u
-            })
-            .collect();
-        all_img_url.reverse();
+    /// 
+    /// Pages are retried according to the client's
+    /// [`RetryPolicy`](crate::client::RetryPolicy). Pages that keep
+    /// failing are requested again from a freshly resolved
+    /// MangaDex@Home server, which also replaces expired tokens.
+    /// 
+    /// The pages are downloaded in the client's
+    /// [`Quality`](crate::manga::Quality). With
+    /// `Quality::OriginalWithFallback`, pages that keep failing are
+    /// first tried from the data saver before a new server is
+    /// requested.
+    /// 
+    /// The images are returned in the order of the chapter's pages.
+    /// 
+    /// Unless the client's `verify_pages` is turned off, a chapter
+    /// whose server lists a different number of pages than the
+    /// chapter has is not downloaded, and [`DexError::Integrity`] is
+    /// returned.
+    /// 
+    /// With a [`PageCache`](crate::cache::PageCache), pages already
+    /// in the cache are not downloaded again, and a chapter that was
+    /// completed before in a quality that meets the client's is
+    /// returned without making any request.
+    /// 
+    /// When the client is cancelled, the pages being downloaded are
+    /// finished and [`DexError::Cancelled`] is returned.
+    pub async fn download(&self, client: &DexClient, report: bool) -> Result<Vec<MangaImage>, DexError> {
+        let quality = client.config().quality;
+        if let Some(images) = client.page_cache().and_then(|cache| cache.completed(&self.id, quality)) {
+            debug!("Chapter {} is complete in the cache", &self.id);
+            client.emit(Progress::ChapterStarted {
+                chapter_id: self.id.clone(),
+                chapter_no: self.get_chapter_number().clone(),
+                pages: images.len(),
+            });
+            for image in images.iter() {
+                client.emit(Progress::PageReused { chapter_id: self.id.clone(), page_no: image.page_no });
+            }
+            client.emit(Progress::ChapterFinished { chapter_id: self.id.clone() });
+            return Ok(images);
+        }
+
+        let max_refreshes = client.config().retry.max_refreshes;
+        let mut data_saver = quality == Quality::DataSaver;
+
+        let mut at_home_info = self.get_manga_at_home_info(client).await?;
+        let page_count = at_home_info.page_count(data_saver) as i32;
+        if client.config().verify_pages && page_count != self.get_pages() {
+            return Err(DexError::Integrity {
+                url: client.api_url(&format!("/at-home/server/{}", &self.id)),
+                reason: format!("chapter {} has {} pages, but its server lists {}", &self.id, self.get_pages(), page_count),
+            });
+        }
+
+        client.emit(Progress::ChapterStarted {
+            chapter_id: self.id.clone(),
+            chapter_no: self.get_chapter_number().clone(),
+            pages: page_count as usize,
+        });
+
+        let mut pending: Vec<i32> = (0..page_count).collect();
+        let mut images: Vec<MangaImage> = Vec::new();
+        let mut files: BTreeMap<i32, String> = BTreeMap::new();
+        let mut refreshes = 0;
+
+        loop {
+            let mut last_error = None;
+            let mut all_img_url: Vec<(i32, String)> = Vec::new();
+            let mut missing: Vec<i32> = Vec::new();
+            for page_no in pending.iter() {
+                let page = (
+                    at_home_info.page_file(*page_no as usize, data_saver),
+                    at_home_info.page_url(*page_no as usize, data_saver),
+                );
+                let (file, url) = match page {
+                    (Some(file), Some(url)) => (String::from(file), url),
+                    _ => {
+                        missing.push(*page_no);
+                        continue;
+                    },
+                };
+
+                // Pages stored by an earlier run are not downloaded again
+                let cached = client.page_cache()
+                    .and_then(|cache| cache.get(&self.id, &file, *page_no));
+                match cached {
+                    Some(image) => {
+                        client.emit(Progress::PageReused { chapter_id: self.id.clone(), page_no: *page_no });
+                        images.push(image);
+                    },
+                    None => all_img_url.push((*page_no, url)),
+                }
+                files.insert(*page_no, file);
+            }
+
+            let results = connection::async_get_image_batch(client, all_img_url, report)
+                .await;
+
+            pending = missing;
+            if !pending.is_empty() {
+                last_error = Some(DexError::NotFound(format!(
+                    "{} pages of chapter {} on {}",
+                    pending.len(), &self.id, &at_home_info.base_url
+                )));
+            }
+            for (page_no, result) in results {
+                match result {
+                    Ok(image) => {
+                        let image = match client.page_cache() {
+                            Some(cache) => cache.store(&self.id, &files[&page_no], image).await?,
+                            None => image,
+                        };
+                        images.push(image);
+                    },
+                    Err(e) => {
+                        pending.push(page_no);
+                        last_error = Some(e);
+                    },
+                }
+            }
 
-        let result = connection::async_get_image_batch(&mut all_img_url, 5, report)
-            .await;
-        
-        result
+            match last_error {
+                None => {
+                    // Pages retried after a refresh come in last
+                    images.sort_by_key(|image| image.page_no);
+                    if let Some(cache) = client.page_cache() {
+                        let downloaded = match (quality, data_saver) {
+                            (Quality::DataSaver, _) => Quality::DataSaver,
+                            (_, true) => Quality::OriginalWithFallback,
+                            (_, false) => Quality::Original,
+                        };
+                        cache.complete(&self.id, files.into_values().collect(), downloaded)?;
+                    }
+                    client.emit(Progress::ChapterFinished { chapter_id: self.id.clone() });
+                    return Ok(images);
+                },
+                // Pages finished before the cancellation stay in the cache
+                Some(_) if client.is_cancelled() => return Err(DexError::Cancelled),
+                Some(e) if quality == Quality::OriginalWithFallback && !data_saver => {
+                    data_saver = true;
+                    warn!(
+                        "{} pages of chapter {} failed ({}), falling back to the data saver",
+                        pending.len(), &self.id, e
+                    );
+                },
+                Some(e) if refreshes >= max_refreshes => return Err(e),
+                Some(e) => {
+                    refreshes += 1;
+                    warn!(
+                        "{} pages of chapter {} failed ({}), requesting a new server",
+                        pending.len(), &self.id, e
+                    );
+                    at_home_info = self.get_manga_at_home_info(client).await?;
+                },
+            }
+        }
     }
 }
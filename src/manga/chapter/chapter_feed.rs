@@ -0,0 +1,257 @@
+use async_trait::async_trait;
+use futures::stream::{self, Stream, TryStreamExt};
+use log::warn;
+
+use crate::client::DexClient;
+use crate::connection::AsyncGet;
+use crate::error::DexError;
+
+use super::chapter::Chapter;
+use super::chapter_list::{ChapterList, Pagination};
+
+/// The number of chapters requested per page of the feed, the most
+/// the API allows
+const PAGE_SIZE: usize = 100;
+
+/// How far into a feed the API lists chapters, as `offset + limit`
+/// may not go past it
+const MAX_OFFSET: usize = 10_000;
+
+/// A field the chapters of a feed can be ordered by
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeedOrder {
+    Volume,
+    Chapter,
+    CreatedAt,
+    UpdatedAt,
+    PublishAt,
+    ReadableAt,
+}
+
+impl FeedOrder {
+    /// Returns the name of the field in the query
+    fn as_str(&self) -> &'static str {
+        match self {
+            FeedOrder::Volume => "volume",
+            FeedOrder::Chapter => "chapter",
+            FeedOrder::CreatedAt => "createdAt",
+            FeedOrder::UpdatedAt => "updatedAt",
+            FeedOrder::PublishAt => "publishAt",
+            FeedOrder::ReadableAt => "readableAt",
+        }
+    }
+}
+
+/// The direction a feed is ordered in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SortDirection {
+    Ascending,
+    Descending,
+}
+
+impl SortDirection {
+    fn as_str(&self) -> &'static str {
+        match self {
+            SortDirection::Ascending => "asc",
+            SortDirection::Descending => "desc",
+        }
+    }
+}
+
+/// The chapters of a manga from `/manga/{id}/feed`, requested page
+/// by page as they are needed.
+///
+/// Pages hold as many chapters as the API allows. As the API does
+/// not list chapters past its 10000th, the feed ends there, with a
+/// warning if the manga has more.
+///
+/// # Examples
+/// ```no_run
+/// use dexloader::client::DexClient;
+/// use dexloader::manga::{ChapterFeed, FeedOrder, SortDirection};
+///
+/// #[tokio::main]
+/// async fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let feed = ChapterFeed::new(DexClient::default(), "259dfd8a-f06a-4825-8fa6-a2dcd7274230")
+///         .with_languages(vec![String::from("en")])
+///         .with_order(FeedOrder::Volume, SortDirection::Ascending)
+///         .with_order(FeedOrder::Chapter, SortDirection::Ascending);
+///     let chapters = feed.collect().await?;
+///     println!("{} chapters", chapters.data.len());
+///     Ok(())
+/// }
+/// ```
+pub struct ChapterFeed {
+    client: DexClient,
+    manga_id: String,
+    languages: Vec<String>,
+    future_updates: bool,
+    empty_pages: bool,
+    order: Vec<(FeedOrder, SortDirection)>,
+    start: usize,
+    count: Option<usize>,
+    offset: usize,
+    total: Option<usize>,
+    finished: bool,
+}
+
+#[async_trait]
+impl AsyncGet for ChapterFeed {
+    fn client(&self) -> &DexClient {
+        &self.client
+    }
+}
+
+impl ChapterFeed {
+    /// Creates the feed of every chapter of a manga, in every
+    /// language, ordered by chapter number.
+    ///
+    /// Chapters that are not readable yet are included, and
+    /// chapters without pages, such as the ones hosted elsewhere,
+    /// are left out.
+    pub fn new(client: DexClient, manga_id: &str) -> Self {
+        ChapterFeed {
+            client,
+            manga_id: String::from(manga_id),
+            languages: Vec::new(),
+            future_updates: true,
+            empty_pages: false,
+            order: Vec::new(),
+            start: 0,
+            count: None,
+            offset: 0,
+            total: None,
+            finished: false,
+        }
+    }
+
+    /// Only lists the chapters translated into the given languages,
+    /// or the chapters in every language if none are given
+    pub fn with_languages(mut self, languages: Vec<String>) -> Self {
+        self.languages = languages;
+        self
+    }
+
+    /// Whether chapters that are not readable yet are listed
+    pub fn with_future_updates(mut self, include: bool) -> Self {
+        self.future_updates = include;
+        self
+    }
+
+    /// Whether chapters without pages are listed
+    pub fn with_empty_pages(mut self, include: bool) -> Self {
+        self.empty_pages = include;
+        self
+    }
+
+    /// Orders the chapters by a field. The first order given comes
+    /// first, and the later ones break its ties.
+    pub fn with_order(mut self, field: FeedOrder, direction: SortDirection) -> Self {
+        self.order.push((field, direction));
+        self
+    }
+
+    /// Skips the first `offset` chapters of the feed, and lists at
+    /// most `count` chapters after them
+    pub fn with_range(mut self, offset: usize, count: Option<usize>) -> Self {
+        self.start = offset;
+        self.offset = offset;
+        self.count = count;
+        self
+    }
+
+    /// Returns the number of chapters the API has for the feed,
+    /// once a page was requested
+    pub fn get_total(&self) -> Option<usize> {
+        self.total
+    }
+
+    fn construct_request_url(&self, limit: usize) -> String {
+        let mut req_url = self.client.api_url(&format!("/manga/{}/feed?limit={}", &self.manga_id, limit));
+        if self.offset != 0 {
+            req_url.push_str(&format!("&offset={}", self.offset));
+        }
+        for language in self.languages.iter() {
+            req_url.push_str("&translatedLanguage[]=");
+            req_url.push_str(language);
+        }
+        req_url.push_str(&format!("&includeFutureUpdates={}", self.future_updates as u8));
+        req_url.push_str(&format!("&includeEmptyPages={}", self.empty_pages as u8));
+
+        if self.order.is_empty() {
+            req_url.push_str("&order[chapter]=asc");
+        }
+        for (field, direction) in self.order.iter() {
+            req_url.push_str(&format!("&order[{}]={}", field.as_str(), direction.as_str()));
+        }
+        req_url.push_str("&includes[]=scanlation_group");
+
+        req_url
+    }
+
+    /// Requests the next page of the feed, or returns `None` once
+    /// every chapter was listed.
+    pub async fn next_page(&mut self) -> Result<Option<ChapterList>, DexError> {
+        if self.finished {
+            return Ok(None);
+        }
+
+        let listed = self.offset - self.start;
+        let mut limit = PAGE_SIZE.min(MAX_OFFSET.saturating_sub(self.offset));
+        if let Some(count) = self.count {
+            limit = limit.min(count.saturating_sub(listed));
+        }
+        if limit == 0 {
+            self.finished = true;
+            return Ok(None);
+        }
+
+        self.client.check_cancelled()?;
+        let page = self.async_get_json::<ChapterList>(&self.construct_request_url(limit)).await?;
+        let total = page.pagination.total.max(0) as usize;
+        self.total = Some(total);
+        self.offset += page.data.len();
+
+        let wanted = self.count.map_or(total, |count| total.min(self.start + count));
+        if page.data.is_empty() || self.offset >= wanted {
+            self.finished = true;
+        } else if self.offset >= MAX_OFFSET {
+            warn!(
+                "Manga {} has {} chapters, but the API only lists the first {}",
+                &self.manga_id, total, MAX_OFFSET
+            );
+            self.finished = true;
+        }
+        Ok(Some(page))
+    }
+
+    /// Turns the feed into a stream of its chapters, requesting the
+    /// pages as the stream is read
+    pub fn into_stream(self) -> impl Stream<Item = Result<Chapter, DexError>> {
+        stream::try_unfold(self, |mut feed| async move {
+            let page = feed.next_page().await?;
+            let chapters = page.map(|page| stream::iter(page.data.into_iter().map(Ok::<Chapter, DexError>)));
+            Ok::<_, DexError>(chapters.map(|chapters| (chapters, feed)))
+        })
+        .try_flatten()
+    }
+
+    /// Requests every page of the feed and returns its chapters in
+    /// a single list
+    pub async fn collect(mut self) -> Result<ChapterList, DexError> {
+        let mut data: Vec<Chapter> = Vec::new();
+        while let Some(page) = self.next_page().await? {
+            data.extend(page.data);
+        }
+
+        let total = self.total.unwrap_or(data.len());
+        Ok(ChapterList {
+            pagination: Pagination {
+                limit: data.len() as i32,
+                offset: self.start as i32,
+                total: total as i32,
+            },
+            data,
+        })
+    }
+}
@@ -1,6 +1,71 @@
 use serde::{Serialize, Deserialize};
+use log::{info, warn};
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::str::FromStr;
 
 use super::chapter::Chapter;
+use super::chapter_number::{ChapterNumber, UnnumberedPlacement};
+use super::volume::Volume;
+
+/// How one upload is picked among the uploads of the same chapter,
+/// such as the translations of several scanlation groups.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum DuplicateStrategy {
+    /// The upload of the most preferred group, or the first one
+    /// if no preferred group uploaded the chapter
+    #[default]
+    PreferredGroup,
+    /// The upload with the most pages
+    MostPages,
+    /// The most recent upload
+    NewestUpload,
+    /// Every upload is kept
+    KeepAll,
+}
+
+impl FromStr for DuplicateStrategy {
+    type Err = String;
+
+    /// Parses `preferred-group`, `most-pages`, `newest` or `all`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "preferred-group" => Ok(DuplicateStrategy::PreferredGroup),
+            "most-pages" => Ok(DuplicateStrategy::MostPages),
+            "newest" => Ok(DuplicateStrategy::NewestUpload),
+            "all" => Ok(DuplicateStrategy::KeepAll),
+            _ => Err(format!("unknown strategy {}, expected preferred-group, most-pages, newest or all", s)),
+        }
+    }
+}
+
+/// Which translations of a manga are requested.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum LanguageMode {
+    /// The translations in the preferred languages, picking the
+    /// most preferred one available for every chapter
+    #[default]
+    Preferred,
+    /// Only the chapters in the original language of the manga
+    Original,
+    /// The chapters in every language
+    All,
+}
+
+impl FromStr for LanguageMode {
+    type Err = String;
+
+    /// Parses `preferred`, `original` or `all`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "preferred" => Ok(LanguageMode::Preferred),
+            "original" => Ok(LanguageMode::Original),
+            "all" => Ok(LanguageMode::All),
+            _ => Err(format!("unknown language mode {}, expected preferred, original or all", s)),
+        }
+    }
+}
 
 /// The list of manga chapters and its pagination information.
 #[derive(Serialize, Deserialize)]
@@ -21,7 +86,220 @@ pub struct Pagination {
 }
 
 impl ChapterList {
-    pub fn sort_chapters(&mut self) {
-        self.data.sort_by(|chapter1, chapter2| chapter1.get_chapter_number().partial_cmp(&chapter2.get_chapter_number()).unwrap());
+    /// Sorts the chapters by their number, placing the unnumbered
+    /// ones as given. The chapters assigned to a volume are then
+    /// ordered by volume first, among the places they took, as the
+    /// numbers of some series start over with every volume. Uploads
+    /// of the same chapter keep their order.
+    pub fn sort_chapters(&mut self, unnumbered: UnnumberedPlacement) {
+        self.data.sort_by(|chapter1, chapter2| unnumbered.compare(chapter1.get_chapter_number(), chapter2.get_chapter_number()));
+
+        let in_volume: Vec<bool> = self.data.iter()
+            .map(|chapter| chapter.get_volume().is_assigned())
+            .collect();
+        let (mut assigned, unassigned): (Vec<Chapter>, Vec<Chapter>) = std::mem::take(&mut self.data)
+            .into_iter()
+            .partition(|chapter| chapter.get_volume().is_assigned());
+        assigned.sort_by(|chapter1, chapter2| {
+            chapter1.get_volume().cmp(chapter2.get_volume())
+                .then_with(|| unnumbered.compare(chapter1.get_chapter_number(), chapter2.get_chapter_number()))
+        });
+
+        let mut assigned = assigned.into_iter();
+        let mut unassigned = unassigned.into_iter();
+        self.data = in_volume.into_iter()
+            .filter_map(|in_volume| if in_volume { assigned.next() } else { unassigned.next() })
+            .collect();
+    }
+
+    /// Returns the volumes of the chapters in order, with the
+    /// chapters not assigned to a volume last
+    pub fn get_volumes(&self) -> Vec<Volume> {
+        let mut volumes: Vec<Volume> = self.data.iter()
+            .map(|chapter| chapter.get_volume().clone())
+            .collect();
+        volumes.sort();
+        volumes.dedup();
+        volumes
+    }
+
+    /// Groups the chapters by volume, in the order of
+    /// [`get_volumes`](ChapterList::get_volumes). The chapters of
+    /// each volume keep their order in the list.
+    pub fn group_by_volume(&self) -> Vec<(Volume, Vec<&Chapter>)> {
+        self.get_volumes()
+            .into_iter()
+            .map(|volume| {
+                let chapters = self.data.iter()
+                    .filter(|chapter| *chapter.get_volume() == volume)
+                    .collect();
+                (volume, chapters)
+            })
+            .collect()
+    }
+
+    /// Removes the chapters translated by any of the `blocked`
+    /// groups, and puts the uploads of the `preferred` groups first
+    /// among the uploads of the same chapter, in the order the
+    /// groups are given. Groups are given by id or by name.
+    /// 
+    /// The uploads of a chapter are moved to the place of its first
+    /// upload.
+    pub fn filter_groups<S: AsRef<str>>(&mut self, preferred: &[S], blocked: &[S]) {
+        self.data.retain(|chapter| {
+            let groups = chapter.get_groups();
+            match groups.iter().find(|group| group.position_in(blocked).is_some()) {
+                Some(group) => {
+                    info!("Skipping chapter {} ({}) by blocked group {}", chapter.get_chapter_number(), &chapter.id, &group.name);
+                    false
+                },
+                None => true,
+            }
+        });
+
+        if preferred.is_empty() {
+            return;
+        }
+        // Sorting the uploads of each chapter, leaving the chapters
+        // in their order
+        let mut runs = chapter_runs(std::mem::take(&mut self.data));
+        for uploads in runs.iter_mut() {
+            uploads.sort_by_key(|chapter| group_rank(chapter, preferred));
+        }
+        self.data = runs.into_iter().flatten().collect();
+    }
+
+    /// Keeps only the uploads in the most preferred of `languages`
+    /// among the uploads of the same chapter, so that every chapter
+    /// is read in the best language it was translated into.
+    /// 
+    /// The uploads kept are moved to the place of the chapter's
+    /// first upload.
+    pub fn keep_best_language<S: AsRef<str>>(&mut self, languages: &[S]) {
+        let mut kept: Vec<Chapter> = Vec::with_capacity(self.data.len());
+        for mut uploads in chapter_runs(std::mem::take(&mut self.data)) {
+            let best = uploads.iter()
+                .map(|chapter| language_rank(chapter, languages))
+                .min()
+                .unwrap_or(languages.len());
+            let count = uploads.len();
+            uploads.retain(|chapter| language_rank(chapter, languages) == best);
+            if uploads.len() < count {
+                info!(
+                    "Chapter {}: keeping the {} translation, skipping {} other upload(s)",
+                    uploads[0].get_chapter_number(),
+                    uploads[0].get_language().unwrap_or("unknown"),
+                    count - uploads.len()
+                );
+            }
+            kept.extend(uploads);
+        }
+        self.data = kept;
+    }
+
+    /// Keeps a single upload of every numbered chapter of each
+    /// volume, picked with the given strategy. An upload without a
+    /// volume is taken for an upload of the same number in any
+    /// volume, and unnumbered chapters are never taken for uploads
+    /// of the same chapter. The
+    /// `preferred` groups are only used by
+    /// [`DuplicateStrategy::PreferredGroup`].
+    /// 
+    /// The upload kept takes the place of the chapter's first
+    /// upload.
+    pub fn remove_duplicates<S: AsRef<str>>(&mut self, strategy: DuplicateStrategy, preferred: &[S]) {
+        if strategy == DuplicateStrategy::KeepAll {
+            return;
+        }
+
+        let mut kept: Vec<Chapter> = Vec::with_capacity(self.data.len());
+        for mut uploads in chapter_runs(std::mem::take(&mut self.data)) {
+            if uploads.len() == 1 {
+                kept.extend(uploads);
+                continue;
+            }
+
+            let picked = match strategy {
+                DuplicateStrategy::PreferredGroup => first_best(&uploads, |chapter| Reverse(group_rank(chapter, preferred))),
+                DuplicateStrategy::MostPages => first_best(&uploads, Chapter::get_pages),
+                DuplicateStrategy::NewestUpload => first_best(&uploads, |chapter| chapter.get_upload_date().map(String::from)),
+                DuplicateStrategy::KeepAll => 0,
+            };
+            let chapter = uploads.swap_remove(picked);
+            let groups: Vec<String> = chapter.get_groups().into_iter().map(|group| group.name).collect();
+            warn!(
+                "Chapter {}: picked {} by {} ({} pages, uploaded {}) over {} other upload(s)",
+                chapter.get_chapter_number(),
+                &chapter.id,
+                if groups.is_empty() { String::from("no group") } else { groups.join(" & ") },
+                chapter.get_pages(),
+                chapter.get_upload_date().unwrap_or("unknown"),
+                uploads.len()
+            );
+            kept.push(chapter);
+        }
+        self.data = kept;
+    }
+}
+
+/// Splits the chapters into the runs of uploads of the same
+/// chapter, in the order of their first upload. Uploads of the same
+/// number are of the same chapter unless both are assigned to
+/// different volumes, as groups often leave the volume out. Every
+/// unnumbered chapter makes a run of its own.
+fn chapter_runs(chapters: Vec<Chapter>) -> Vec<Vec<Chapter>> {
+    let mut runs: Vec<Vec<Chapter>> = Vec::new();
+    // The runs of every chapter number met so far
+    let mut numbers: HashMap<ChapterNumber, Vec<usize>> = HashMap::new();
+    for chapter in chapters {
+        if !chapter.get_chapter_number().is_numbered() {
+            runs.push(vec![chapter]);
+            continue;
+        }
+
+        let candidates = numbers.entry(chapter.get_chapter_number().clone()).or_default();
+        let volume = chapter.get_volume();
+        let run = candidates.iter()
+            .copied()
+            .find(|&i| runs[i].iter().all(|upload| {
+                !upload.get_volume().is_assigned() || !volume.is_assigned() || upload.get_volume() == volume
+            }));
+        match run {
+            Some(i) => runs[i].push(chapter),
+            None => {
+                candidates.push(runs.len());
+                runs.push(vec![chapter]);
+            },
+        }
+    }
+    runs
+}
+
+/// Returns the position of the first of `groups` that uploaded the
+/// chapter, or the number of groups if none did
+fn group_rank<S: AsRef<str>>(chapter: &Chapter, groups: &[S]) -> usize {
+    chapter.get_groups()
+        .iter()
+        .filter_map(|group| group.position_in(groups))
+        .min()
+        .unwrap_or(groups.len())
+}
+
+/// Returns the position of the chapter's language in `languages`,
+/// or the number of languages if it is not one of them
+fn language_rank<S: AsRef<str>>(chapter: &Chapter, languages: &[S]) -> usize {
+    languages.iter()
+        .position(|language| Some(language.as_ref()) == chapter.get_language())
+        .unwrap_or(languages.len())
+}
+
+/// Returns the index of the first chapter with the greatest key
+fn first_best<K: Ord>(chapters: &[Chapter], key: impl Fn(&Chapter) -> K) -> usize {
+    let mut best = 0;
+    for (i, chapter) in chapters.iter().enumerate().skip(1) {
+        if key(chapter) > key(&chapters[best]) {
+            best = i;
+        }
     }
+    best
 }
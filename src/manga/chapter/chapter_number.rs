@@ -0,0 +1,234 @@
+use serde::{Serialize, Deserialize, Deserializer, Serializer};
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+/// The number of a chapter as MangaDex sends it, such as `12`,
+/// `12.5` or `12a`, or none for oneshots and other unnumbered
+/// chapters.
+///
+/// Numbers are ordered naturally: the number they start with is
+/// compared as a decimal, and the rest piece by piece, so that
+/// `2 < 10 < 10a < 10.5 < 11`. Unnumbered chapters come after every
+/// numbered one.
+///
+/// # Examples
+/// ```
+/// use dexloader::manga::ChapterNumber;
+///
+/// assert!(ChapterNumber::from("2") < ChapterNumber::from("10"));
+/// assert!(ChapterNumber::from("10a") < ChapterNumber::from("10.5"));
+/// assert_eq!(ChapterNumber::from("07"), ChapterNumber::from("7"));
+/// assert!(ChapterNumber::from("11") < ChapterNumber::none());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ChapterNumber {
+    raw: Option<String>,
+}
+
+/// A piece of a chapter number
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Segment {
+    /// A number, as its integer digits without leading zeros and
+    /// its fractional digits without trailing zeros
+    Number(Digits, String),
+    /// Anything between the numbers, in lowercase
+    Text(String),
+}
+
+/// Integer digits, ordered by their value
+#[derive(PartialEq, Eq, Hash)]
+struct Digits(String);
+
+impl Ord for Digits {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.len().cmp(&other.0.len()).then_with(|| self.0.cmp(&other.0))
+    }
+}
+
+impl PartialOrd for Digits {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl ChapterNumber {
+    /// The number of an unnumbered chapter
+    pub fn none() -> Self {
+        ChapterNumber { raw: None }
+    }
+
+    /// Returns the number as MangaDex sent it
+    pub fn as_str(&self) -> Option<&str> {
+        self.raw.as_deref()
+    }
+
+    /// Whether the chapter has a number
+    pub fn is_numbered(&self) -> bool {
+        self.raw.is_some()
+    }
+
+    /// Returns the number as a float, if it is one
+    pub fn as_f32(&self) -> Option<f32> {
+        f32::from_str(self.raw.as_deref()?.trim()).ok()
+    }
+
+    /// Returns the number with the characters that do not belong
+    /// in a path replaced, or `unnumbered` for unnumbered chapters
+    pub fn to_file_name(&self) -> String {
+        match &self.raw {
+            Some(raw) => raw.trim()
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() || c == '.' || c == '-' { c } else { '_' })
+                .collect(),
+            None => String::from("unnumbered"),
+        }
+    }
+
+    fn segments(&self) -> Vec<Segment> {
+        let raw = self.raw.as_deref().unwrap_or("").trim().to_lowercase();
+        let mut segments = Vec::new();
+        let mut chars = raw.chars().peekable();
+
+        while let Some(&c) = chars.peek() {
+            if c.is_ascii_digit() {
+                let mut integer = String::new();
+                while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                    integer.push(digit);
+                }
+
+                // A dot followed by digits makes a decimal
+                let mut fraction = String::new();
+                let mut lookahead = chars.clone();
+                if lookahead.next() == Some('.') && lookahead.peek().is_some_and(char::is_ascii_digit) {
+                    chars.next();
+                    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                        fraction.push(digit);
+                    }
+                }
+
+                let integer = integer.trim_start_matches('0');
+                let fraction = fraction.trim_end_matches('0');
+                segments.push(Segment::Number(Digits(String::from(integer)), String::from(fraction)));
+            } else {
+                let mut text = String::new();
+                while let Some(c) = chars.next_if(|c| !c.is_ascii_digit()) {
+                    text.push(c);
+                }
+                let text = text.trim();
+                if !text.is_empty() {
+                    segments.push(Segment::Text(String::from(text)));
+                }
+            }
+        }
+
+        segments
+    }
+}
+
+impl From<&str> for ChapterNumber {
+    /// Reads a chapter number, where an empty one means the
+    /// chapter is unnumbered
+    fn from(raw: &str) -> Self {
+        let raw = raw.trim();
+        if raw.is_empty() {
+            ChapterNumber::none()
+        } else {
+            ChapterNumber { raw: Some(String::from(raw)) }
+        }
+    }
+}
+
+impl fmt::Display for ChapterNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.raw {
+            Some(raw) => write!(f, "{}", raw),
+            None => write!(f, "unnumbered"),
+        }
+    }
+}
+
+impl Ord for ChapterNumber {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.is_numbered(), other.is_numbered()) {
+            (true, true) => self.segments().cmp(&other.segments()),
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            (false, false) => Ordering::Equal,
+        }
+    }
+}
+
+impl PartialOrd for ChapterNumber {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for ChapterNumber {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ChapterNumber {}
+
+impl Hash for ChapterNumber {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.is_numbered().hash(state);
+        if self.is_numbered() {
+            self.segments().hash(state);
+        }
+    }
+}
+
+impl Serialize for ChapterNumber {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.raw.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ChapterNumber {
+    /// Reads the number from a string, where null or an empty
+    /// string means the chapter is unnumbered
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw: Option<String> = Deserialize::deserialize(deserializer)?;
+        Ok(raw.as_deref().map(ChapterNumber::from).unwrap_or_default())
+    }
+}
+
+/// Where unnumbered chapters, such as oneshots, are placed among
+/// the numbered ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum UnnumberedPlacement {
+    /// Before the first numbered chapter
+    First,
+    /// After the last numbered chapter
+    #[default]
+    Last,
+}
+
+impl UnnumberedPlacement {
+    /// Compares two chapter numbers, placing the unnumbered ones
+    pub fn compare(&self, number1: &ChapterNumber, number2: &ChapterNumber) -> Ordering {
+        match (self, number1.is_numbered() != number2.is_numbered()) {
+            (UnnumberedPlacement::First, true) => number1.cmp(number2).reverse(),
+            _ => number1.cmp(number2),
+        }
+    }
+}
+
+impl FromStr for UnnumberedPlacement {
+    type Err = String;
+
+    /// Parses `first` or `last`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "first" => Ok(UnnumberedPlacement::First),
+            "last" => Ok(UnnumberedPlacement::Last),
+            _ => Err(format!("unknown placement {}, expected first or last", s)),
+        }
+    }
+}
@@ -1,30 +1,81 @@
 extern crate image;
-use image::ImageResult;
+
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::PathBuf;
+
+use crate::error::DexError;
+use crate::manga::{ChapterNumber, ScanlationGroup};
 
 pub struct ChapterImage {
-    pub chapter_no: f32,
+    pub chapter_no: ChapterNumber,
     pub chapter_title: String,
     pub target_name: String,
     pub images: Vec<MangaImage>,
+    /// The scanlation groups that translated the chapter
+    pub groups: Vec<ScanlationGroup>,
+    /// The language the chapter was translated into
+    pub language: Option<String>,
+}
+
+/// Where the bytes of a downloaded image are kept.
+#[derive(Clone)]
+pub enum ImageData {
+    /// The image is held in memory
+    Memory(Vec<u8>),
+    /// The image was spooled to a file and is read back on demand
+    File(PathBuf),
+}
+
+impl ImageData {
+    /// Opens the image for reading. Spooled images are streamed
+    /// from their file instead of being loaded at once.
+    pub fn reader(&self) -> Result<Box<dyn Read + '_>, DexError> {
+        match self {
+            ImageData::Memory(bytes) => Ok(Box::new(bytes.as_slice())),
+            ImageData::File(path) => Ok(Box::new(File::open(path)?)),
+        }
+    }
 }
 
 /// A wrapper for DynamicImage with page number included.
+#[derive(Clone)]
 pub struct MangaImage {
     pub page_no: i32,
-    pub image: Vec<u8>,
+    pub image: ImageData,
 }
 
 impl MangaImage {
     /// Creates a MangaImage object from the page number
     /// of the image and the image itself.
     pub fn new(page_no: i32, image: Vec<u8>) -> Self {
-        MangaImage { page_no, image }
+        MangaImage { page_no, image: ImageData::Memory(image) }
+    }
+
+    /// Creates a MangaImage object whose image is stored
+    /// in a file.
+    pub fn from_file(page_no: i32, path: PathBuf) -> Self {
+        MangaImage { page_no, image: ImageData::File(path) }
+    }
+
+    /// Returns the bytes of the image, reading them from its
+    /// file if it was spooled.
+    pub fn bytes(&self) -> Result<Vec<u8>, DexError> {
+        match &self.image {
+            ImageData::Memory(bytes) => Ok(bytes.clone()),
+            ImageData::File(path) => Ok(fs::read(path)?),
+        }
+    }
+
+    /// Takes the image out, leaving an empty image behind
+    pub fn take(&mut self) -> ImageData {
+        std::mem::replace(&mut self.image, ImageData::Memory(Vec::new()))
     }
 
     /// Saves the image to a path
-    pub fn save(&self, file_name: String) -> ImageResult<()> {
-        let image = image::load_from_memory(&self.image)
-            .expect("Failed to parse image from bytes");
-        image.save(file_name)
+    pub fn save(&self, file_name: String) -> Result<(), DexError> {
+        let image = image::load_from_memory(&self.bytes()?)?;
+        image.save(file_name)?;
+        Ok(())
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,67 @@
+use serde::{Serialize, Deserialize};
+
+use std::fmt;
+
+use super::chapter_number::ChapterNumber;
+
+/// The volume a chapter belongs to, as MangaDex sends it, or none
+/// for chapters that are not assigned to a volume yet.
+///
+/// Volumes are ordered naturally like [`ChapterNumber`], with the
+/// unassigned chapters after every volume.
+///
+/// # Examples
+/// ```
+/// use dexloader::manga::Volume;
+///
+/// assert!(Volume::from("2") < Volume::from("10"));
+/// assert!(Volume::from("10") < Volume::unassigned());
+/// assert_eq!(Volume::from("3").to_string(), "Volume 3");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Volume(ChapterNumber);
+
+impl Volume {
+    /// The volume of the chapters not assigned to one
+    pub fn unassigned() -> Self {
+        Volume(ChapterNumber::none())
+    }
+
+    /// Returns the volume as MangaDex sent it
+    pub fn as_str(&self) -> Option<&str> {
+        self.0.as_str()
+    }
+
+    /// Whether the chapters are assigned to a volume
+    pub fn is_assigned(&self) -> bool {
+        self.0.is_numbered()
+    }
+
+    /// Returns a name for the volume that can be used in a path,
+    /// such as `Vol_3`, or `No_volume` for unassigned chapters
+    pub fn to_file_name(&self) -> String {
+        if self.is_assigned() {
+            format!("Vol_{}", self.0.to_file_name())
+        } else {
+            String::from("No_volume")
+        }
+    }
+}
+
+impl From<&str> for Volume {
+    /// Reads a volume, where an empty one means the chapters
+    /// are unassigned
+    fn from(raw: &str) -> Self {
+        Volume(ChapterNumber::from(raw))
+    }
+}
+
+impl fmt::Display for Volume {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.as_str() {
+            Some(volume) => write!(f, "Volume {}", volume),
+            None => write!(f, "No volume"),
+        }
+    }
+}
@@ -0,0 +1,43 @@
+/// The part a person had in making a manga.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CreatorRole {
+    /// Wrote the story
+    Author,
+    /// Drew the art
+    Artist,
+}
+
+impl CreatorRole {
+    /// Returns the role of a relationship type, if it is one
+    pub fn from_relation_type(relation_type: &str) -> Option<Self> {
+        match relation_type {
+            "author" => Some(CreatorRole::Author),
+            "artist" => Some(CreatorRole::Artist),
+            _ => None,
+        }
+    }
+
+    /// Returns the MARC relator code of the role, as used by epubs
+    pub fn marc_code(&self) -> &'static str {
+        match self {
+            CreatorRole::Author => "aut",
+            CreatorRole::Artist => "art",
+        }
+    }
+}
+
+/// An author or artist of a manga, with every role they had
+/// in making it.
+#[derive(Clone, Debug)]
+pub struct Creator {
+    pub id: String,
+    pub name: String,
+    pub roles: Vec<CreatorRole>,
+}
+
+impl Creator {
+    /// Whether the creator had the given role
+    pub fn has_role(&self, role: CreatorRole) -> bool {
+        self.roles.contains(&role)
+    }
+}
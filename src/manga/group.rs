@@ -0,0 +1,23 @@
+/// A scanlation group credited for a chapter.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScanlationGroup {
+    pub id: String,
+    /// The name of the group, or its id if the group was not
+    /// included with the chapter
+    pub name: String,
+}
+
+impl ScanlationGroup {
+    /// Whether the group is the one given, by id or by name
+    /// ignoring case.
+    pub fn matches(&self, group: &str) -> bool {
+        let group = group.trim();
+        self.id == group || self.name.to_lowercase() == group.to_lowercase()
+    }
+
+    /// Returns the position of the first of `groups` this group
+    /// matches
+    pub fn position_in<S: AsRef<str>>(&self, groups: &[S]) -> Option<usize> {
+        groups.iter().position(|group| self.matches(group.as_ref()))
+    }
+}
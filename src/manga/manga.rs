@@ -2,14 +2,20 @@ extern crate async_trait;
 
 use async_trait::async_trait;
 use std::convert::From;
+use std::ops::Range;
 
+use crate::client::DexClient;
+use crate::error::DexError;
 use crate::epub::Book;
 use crate::connection::{self, AsyncGet};
+use crate::progress::Progress;
 
 use super::manga_data::MangaData;
 use super::author::AuthorData;
+use super::creator::{Creator, CreatorRole};
 use super::cover::CoverData;
-use super::chapter::{ChapterList, ChapterImage, MangaImage};
+use super::selection::SelectionOptions;
+use super::chapter::{Chapter, ChapterFeed, ChapterList, ChapterImage, LanguageMode, MangaImage, Volume};
 
 /// Contains the ID of the manga and its URL.
 pub struct Manga {
@@ -20,126 +26,353 @@ pub struct Manga {
     pub chapter_list: Option<ChapterList>,
     pub chapter_images: Vec<ChapterImage>,
     pub author_name: String,
+    /// The authors and artists, once fetched
+    pub creators: Vec<Creator>,
+    /// The cover, once fetched
+    pub cover: Option<MangaImage>,
+    creators_fetched: bool,
+    client: DexClient,
+    selection: SelectionOptions,
 }
 
 #[async_trait]
-impl AsyncGet for Manga {}
+impl AsyncGet for Manga {
+    fn client(&self) -> &DexClient {
+        &self.client
+    }
+}
 
 impl Manga {
-    /// Extracts a manga's id from its URL
+    /// Extracts a manga's id from its URL.
+    /// 
+    /// The id is the segment following `title/`. Anything else is
+    /// taken to be the id itself, so that a bare id also works.
     fn get_manga_id_from_url(url: & str) -> String {
-        let mut splitted_url = url.split("/");
-        String::from(splitted_url.nth(4).unwrap())
+        let mut splitted_url = url.trim().split('/');
+        match splitted_url.position(|segment| segment == "title") {
+            Some(_) => String::from(splitted_url.next().unwrap_or("")),
+            None => String::from(url.trim().trim_matches('/')),
+        }
     }
 
-    /// Construct the URL for fetching information about a manga.
+    /// Creates a manga from its URL that makes its requests
+    /// through the given client.
     /// 
-    /// The offset indicates the "page" number of the request, and
-    /// the chapter limit dictates how many chapters of manga is to
-    /// be fetched per "page".
-    fn construct_manga_chapter_request_url(&self, offset: i32, chapter_limit: Option<i32>, end: Option<i32>) -> String {
-        let mut req_url = String::from("https://api.mangadex.org/chapter?manga=");
-        req_url.push_str(&self.id);
-        req_url.push_str("&translatedLanguage[]=en");
-
-        let count_to_end: i32;
-
-        if offset != 0 {
-            req_url.push_str("&offset=");
-            req_url.push_str(&offset.to_string());
+    /// # Examples
+    /// ```
+    /// use dexloader::client::{ClientConfig, DexClient};
+    /// use dexloader::manga::Manga;
+    /// 
+    /// let client = DexClient::new(ClientConfig::default()).unwrap();
+    /// let url = "https://mangadex.org/title/259dfd8a-f06a-4825-8fa6-a2dcd7274230/yofukashi-no-uta";
+    /// let manga = Manga::with_client(url, client);
+    /// ```
+    pub fn with_client(url: &str, client: DexClient) -> Self {
+        let id = Manga::get_manga_id_from_url(url);
+        Manga { 
+            id,
+            url: String::from(url),
+            data: None,
+            title: String::from(""),
+            chapter_list: None,
+            chapter_images: Vec::new(),
+            author_name: String::from(""),
+            creators: Vec::new(),
+            cover: None,
+            creators_fetched: false,
+            client,
+            selection: SelectionOptions::default(),
         }
+    }
 
-        match end {
-            Some(i) => {
-                count_to_end = i - offset;
-            },
-            None => {
-                count_to_end = -1;
-            },
-        };
+    /// Sets which chapters are kept, and in which language, when
+    /// the chapter list is fetched
+    pub fn with_selection(mut self, selection: SelectionOptions) -> Self {
+        self.selection = selection;
+        self
+    }
 
-        match chapter_limit {
-            Some(i) => {
-                let mut limit = i;
-                if count_to_end != -1 && count_to_end < limit {
-                    limit = count_to_end;
-                }
-                
-                req_url.push_str("&limit=");
-                req_url.push_str(&limit.to_string())
-            },
-            None => (),
-        };
+    /// Returns the client the manga makes its requests with
+    pub fn get_client(&self) -> &DexClient {
+        &self.client
+    }
 
-        req_url.push_str("&order[chapter]=asc");
+    /// Returns which chapters are kept when the chapter list is
+    /// fetched
+    pub fn get_selection(&self) -> &SelectionOptions {
+        &self.selection
+    }
 
-        req_url
+    /// Returns the feed of the manga's chapters in the languages of
+    /// the selection's [`LanguageMode`].
+    /// 
+    /// The feed can be narrowed down further and passed to
+    /// [`get_chapters_from`](Manga::get_chapters_from).
+    pub async fn chapter_feed(&mut self) -> Result<ChapterFeed, DexError> {
+        let languages = self.chapter_languages().await?;
+        Ok(ChapterFeed::new(self.client.clone(), &self.id).with_languages(languages))
     }
 
-    /// Gets the list of all chapters of a manga asynchronously.
+    /// Gets chapters of a manga asynchronously.
+    /// 
+    /// The offset is the position of the first chapter in the feed
+    /// of the manga, and the chapter limit how many chapters are
+    /// fetched from there, up to the end if it is given. Without a
+    /// limit, every chapter after the offset is fetched. The pages
+    /// of the feed are requested behind the scenes.
     /// 
-    /// The offset indicates the "page" number of the request,
-    /// and the chapter limit dictates how many chapters of
-    /// manga to be displayed per "page".
+    /// The chapters are requested in the languages of the
+    /// [`LanguageMode`] of the manga's
+    /// [`SelectionOptions`], and each chapter is kept in the most
+    /// preferred language it is available in, see
+    /// [`ChapterList::keep_best_language`].
+    /// 
+    /// Chapters of the blocked groups are left out, and the uploads
+    /// of the preferred groups come first, see
+    /// [`ChapterList::filter_groups`]. A single upload of each
+    /// chapter is then kept, following the
+    /// [`DuplicateStrategy`](crate::manga::DuplicateStrategy).
     /// 
     /// # Examples
-    /// ```
+    /// ```no_run
     /// use dexloader::manga::Manga;
     /// 
     /// #[tokio::main]
     /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
     ///     let url = "https://mangadex.org/title/efb4278c-a761-406b-9d69-19603c5e4c8b/the-100-girlfriends-who-really-really-really-really-really-love-you";
-    ///     let manga = Manga::from(url);
-    ///     let chapters = manga.get_chapters(None, 0).await;
+    ///     let mut manga = Manga::from(url);
+    ///     manga.get_chapters(None, 0, None).await?;
     ///     Ok(())
     /// }
     /// ```
-    pub async fn get_chapters(&mut self, chapter_limit: Option<i32>, offset: i32, end: Option<i32>) {
-        let request_url = self.construct_manga_chapter_request_url(offset, chapter_limit, end);
+    pub async fn get_chapters(&mut self, chapter_limit: Option<i32>, offset: i32, end: Option<i32>) -> Result<(), DexError> {
+        let offset = offset.max(0);
+        let count = match (chapter_limit, end) {
+            (Some(limit), Some(end)) => Some(limit.min(end - offset)),
+            (limit, end) => limit.or(end.map(|end| end - offset)),
+        };
+        let count = count.map(|count| count.max(0) as usize);
 
-        let mut chapter_list = self.async_get_json::<ChapterList>(&request_url).await;
-        chapter_list.sort_chapters();
+        let feed = self.chapter_feed().await?.with_range(offset as usize, count);
+        self.get_chapters_from(feed).await
+    }
+
+    /// Gets every chapter of a manga, requesting the pages of its
+    /// feed one after the other.
+    /// 
+    /// The chapters are filtered, sorted and deduplicated like the
+    /// ones of [`get_chapters`](Manga::get_chapters).
+    pub async fn get_all_chapters(&mut self) -> Result<(), DexError> {
+        let feed = self.chapter_feed().await?;
+        self.get_chapters_from(feed).await
+    }
+
+    /// Gets every chapter of a feed, filtered, sorted and
+    /// deduplicated like the ones of
+    /// [`get_chapters`](Manga::get_chapters).
+    pub async fn get_chapters_from(&mut self, feed: ChapterFeed) -> Result<(), DexError> {
+        let chapter_list = feed.collect().await?;
+        self.set_chapter_list(chapter_list);
+        Ok(())
+    }
+
+    /// Returns the languages to request the chapters in, following
+    /// the selection's [`LanguageMode`]. No languages means every
+    /// language.
+    async fn chapter_languages(&mut self) -> Result<Vec<String>, DexError> {
+        match self.selection.language_mode {
+            LanguageMode::Preferred => Ok(self.selection.languages.clone()),
+            LanguageMode::All => Ok(Vec::new()),
+            LanguageMode::Original => {
+                let id = self.id.clone();
+                let data = self.fetch_data().await?;
+                let language = data.get_original_language()
+                    .ok_or_else(|| DexError::NotFound(format!("original language of manga {}", id)))?;
+                Ok(vec![String::from(language)])
+            },
+        }
+    }
+
+    /// Sorts, filters and deduplicates a fetched chapter list
+    /// following the manga's [`SelectionOptions`], and keeps it.
+    /// 
+    /// With [`LanguageMode::Preferred`], every chapter is kept in
+    /// the most preferred language it was translated into, before
+    /// the groups and duplicates are looked at.
+    fn set_chapter_list(&mut self, mut chapter_list: ChapterList) {
+        let selection = &self.selection;
+        chapter_list.sort_chapters(selection.unnumbered);
+        if selection.language_mode == LanguageMode::Preferred {
+            chapter_list.keep_best_language(&selection.languages);
+        }
+        chapter_list.filter_groups(&selection.preferred_groups, &selection.blocked_groups);
+        chapter_list.remove_duplicates(selection.duplicates, &selection.preferred_groups);
+        self.client.emit(Progress::ChapterListFetched {
+            count: chapter_list.data.len(),
+            total: chapter_list.pagination.total,
+        });
 
         self.chapter_list = Some(chapter_list);
     }
 
-    async fn get_manga_info(&mut self) -> MangaData {
-        let request_url = format!("https://api.mangadex.org/manga/{}", &self.id);
-        let data = self.async_get_json::<MangaData>(&request_url).await;
+    /// Returns the volumes of the chapter list in order, with the
+    /// chapters not assigned to a volume last
+    pub fn get_volumes(&self) -> Vec<Volume> {
+        self.chapter_list.as_ref()
+            .map(ChapterList::get_volumes)
+            .unwrap_or_default()
+    }
 
-        data
+    /// Fetches the manga with its authors, artists and cover
+    /// included, so that they need no requests of their own.
+    async fn get_manga_info(&mut self) -> Result<MangaData, DexError> {
+        let request_url = self.client.api_url(&format!(
+            "/manga/{}?includes[]=author&includes[]=artist&includes[]=cover_art",
+            &self.id
+        ));
+        self.async_get_json::<MangaData>(&request_url).await
     }
 
-    pub async fn fetch_author(&mut self) {
-        let author_id = self.data.as_ref().unwrap().get_author_id();
-        let request_url = format!("https://api.mangadex.org/author/{}", author_id);
-        let author_data = self.async_get_json::<AuthorData>(&request_url).await;
-        let author_name = author_data.get_name();
-        self.author_name.push_str(author_name);
+    /// Returns the general data of the manga, fetching it first
+    /// if it has not been fetched yet.
+    pub async fn fetch_data(&mut self) -> Result<&MangaData, DexError> {
+        let data = match self.data.take() {
+            Some(data) => data,
+            None => self.get_manga_info().await?,
+        };
+        Ok(self.data.insert(data))
     }
 
-    pub async fn fetch_cover(&self) -> MangaImage {
-        let cover_id = self.data.as_ref().unwrap().get_cover_id();
-        let request_url = format!("https://api.mangadex.org/cover/{}", cover_id);
-        let cover_data = self.async_get_json::<CoverData>(&request_url).await;
-        let cover_file_name = cover_data.get_file_name();
-        
-        let request_url = format!(
-            "https://uploads.mangadex.org/covers/{}/{}",
-            &self.id,
-            &cover_file_name
-        );
-        let cover_image = connection::async_get_image(request_url, 0, false).await.unwrap();
-        cover_image
+    /// Fetches the names of every author and artist into `creators`,
+    /// and the names of the authors into `author_name`.
+    /// 
+    /// A person who is both author and artist is listed once with
+    /// both roles. No request is made for the people included with
+    /// the manga, the others are requested from `/author/{id}`.
+    pub async fn fetch_creators(&mut self) -> Result<(), DexError> {
+        let data = self.fetch_data().await?;
+        let relationships: Vec<(String, CreatorRole, Option<String>)> = data.get_creator_relationships()
+            .into_iter()
+            .filter_map(|relation| {
+                let role = CreatorRole::from_relation_type(&relation.relation_type)?;
+                Some((relation.id.clone(), role, relation.get_name().map(String::from)))
+            })
+            .collect();
+
+        let mut creators: Vec<Creator> = Vec::new();
+        for (id, role, name) in relationships {
+            if let Some(creator) = creators.iter_mut().find(|creator| creator.id == id) {
+                if !creator.has_role(role) {
+                    creator.roles.push(role);
+                }
+                continue;
+            }
+
+            let name = match name {
+                Some(name) => name,
+                None => {
+                    let request_url = self.client.api_url(&format!("/author/{}", id));
+                    let author_data = self.async_get_json::<AuthorData>(&request_url).await?;
+                    String::from(author_data.get_name())
+                },
+            };
+            creators.push(Creator { id, name, roles: vec![role] });
+        }
+
+        let authors: Vec<&str> = creators.iter()
+            .filter(|creator| creator.has_role(CreatorRole::Author))
+            .map(|creator| creator.name.as_str())
+            .collect();
+        self.author_name = authors.join(", ");
+        self.creators = creators;
+        self.creators_fetched = true;
+        Ok(())
     }
 
-    pub async fn download_chapters(&mut self, clear_previous: bool, report: bool) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    /// Downloads the cover image of the manga.
+    /// 
+    /// The cover's filename comes with the manga when it was
+    /// included, and is requested from `/cover/{id}` otherwise.
+    /// The cover is kept with the manga once fetched, and in the
+    /// client's [`ResponseCache`](crate::cache::ResponseCache), if it
+    /// has one, so that it is only downloaded once.
+    pub async fn fetch_cover(&mut self) -> Result<MangaImage, DexError> {
+        if let Some(cover) = &self.cover {
+            return Ok(cover.clone());
+        }
+
+        let data = self.fetch_data().await?;
+        let cover_file_name = match data.get_cover_file_name() {
+            Some(file_name) => String::from(file_name),
+            None => {
+                let cover_id = data.get_cover_id();
+                if cover_id.is_empty() {
+                    return Err(DexError::NotFound(format!("cover of manga {}", &self.id)));
+                }
+
+                let request_url = self.client.api_url(&format!("/cover/{}", cover_id));
+                let cover_data = self.async_get_json::<CoverData>(&request_url).await?;
+                String::from(cover_data.get_file_name())
+            },
+        };
+
+        let cache = self.client.response_cache();
+        let cover = match cache.and_then(|cache| cache.cover(&self.id, &cover_file_name)) {
+            Some(cover) => cover,
+            None => {
+                let request_url = self.client.uploads_url(&format!(
+                    "/covers/{}/{}",
+                    &self.id,
+                    &cover_file_name
+                ));
+                let cover = connection::async_get_image(self.client.clone(), request_url, 0, false).await?;
+                match cache {
+                    Some(cache) => cache.store_cover(&self.id, &cover_file_name, cover).await?,
+                    None => cover,
+                }
+            },
+        };
+        self.cover = Some(cover.clone());
+        Ok(cover)
+    }
+
+    /// Downloads the images of every chapter in the chapter list.
+    /// 
+    /// When the client is cancelled, the chapters finished so far
+    /// are kept in `chapter_images` and [`DexError::Cancelled`] is
+    /// returned. With a page cache, running the download again
+    /// continues where it stopped.
+    pub async fn download_chapters(&mut self, clear_previous: bool, report: bool) -> Result<(), DexError> {
+        self.download_matching(clear_previous, report, |_, _| true).await
+    }
+
+    /// Downloads the images of the chapters of a volume, like
+    /// [`download_chapters`](Manga::download_chapters) does for
+    /// every chapter.
+    pub async fn download_volume(&mut self, volume: &Volume, clear_previous: bool, report: bool) -> Result<(), DexError> {
+        self.download_matching(clear_previous, report, |_, chapter| chapter.get_volume() == volume).await
+    }
+
+    /// Downloads the images of the chapters at the given positions
+    /// of the chapter list, like
+    /// [`download_chapters`](Manga::download_chapters) does for
+    /// every chapter.
+    pub async fn download_range(&mut self, range: Range<usize>, clear_previous: bool, report: bool) -> Result<(), DexError> {
+        self.download_matching(clear_previous, report, |i, _| range.contains(&i)).await
+    }
+
+    async fn download_matching<F>(&mut self, clear_previous: bool, report: bool, wanted: F) -> Result<(), DexError>
+        where F: Fn(usize, &Chapter) -> bool
+    {
         // Fetch general data if not fetched
-        if self.title == "" || self.author_name == "" {
-            self.data = Some(self.get_manga_info().await);
-            
-            self.title.push_str(self.data.as_ref().unwrap().get_title());
-            self.fetch_author().await;
+        if self.title.is_empty() {
+            let languages = self.selection.title_languages.clone();
+            let title = String::from(self.fetch_data().await?.get_title(&languages));
+            self.title = title;
+        }
+        // A manga may have no authors, only artists
+        if !self.creators_fetched {
+            self.fetch_creators().await?;
         }
 
         // Clear previous chapter data
@@ -148,13 +381,26 @@ impl Manga {
         }
         
         // Storing images into the vector
-        for chapter in self.chapter_list.as_ref().unwrap().data.iter() {
-            let chapter_images = chapter.download(report).await;
+        let chapter_list = match &self.chapter_list {
+            Some(chapter_list) => chapter_list,
+            None => return Ok(()),
+        };
+        let chapters: Vec<&Chapter> = chapter_list.data.iter()
+            .enumerate()
+            .filter(|(i, chapter)| wanted(*i, chapter))
+            .map(|(_, chapter)| chapter)
+            .collect();
+        self.client.emit(Progress::ChaptersScheduled { count: chapters.len() });
+        for chapter in chapters {
+            self.client.check_cancelled()?;
+            let chapter_images = chapter.download(&self.client, report).await?;
             let chapter_img = ChapterImage {
-                chapter_no: chapter.get_chapter_number(),
+                chapter_no: chapter.get_chapter_number().clone(),
                 chapter_title: String::from(chapter.get_name()),
                 target_name: chapter.generate_file_name(),
-                images: chapter_images
+                images: chapter_images,
+                groups: chapter.get_groups(),
+                language: chapter.get_language().map(String::from),
             };
 
             self.chapter_images.push(chapter_img);
@@ -163,24 +409,86 @@ impl Manga {
         Ok(())
     }
 
-    pub fn get_total(&self) -> Option<i32> {
-        match &self.chapter_list {
-            Some(c) => {
-                Some(c.pagination.total)
-            },
-            None => None
+    /// Returns the language most of the downloaded chapters were
+    /// translated into, the first one on a tie
+    pub fn get_language(&self) -> Option<String> {
+        let mut counts: Vec<(&str, usize)> = Vec::new();
+        for language in self.chapter_images.iter().filter_map(|ci| ci.language.as_deref()) {
+            match counts.iter_mut().find(|(seen, _)| *seen == language) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((language, 1)),
+            }
         }
+        let most = counts.iter().map(|(_, count)| *count).max()?;
+        counts.into_iter()
+            .find(|(_, count)| *count == most)
+            .map(|(language, _)| String::from(language))
     }
 
-    pub async fn generate_epub(&mut self, output_path: &str) {
-        let mut book = Book::new();
+    pub fn get_total(&self) -> Option<i32> {
+        self.chapter_list.as_ref().map(|c| c.pagination.total)
+    }
+
+    /// Writes the downloaded chapters to an epub.
+    /// 
+    /// The epub is written next to its destination and moved in
+    /// place once complete, so that an interrupted export never
+    /// leaves a partial file at `output_path`.
+    pub async fn generate_epub(&mut self, output_path: &str) -> Result<(), DexError> {
+        let title = self.title.clone();
+        self.generate_titled_epub(&title, output_path).await
+    }
+
+    /// Writes the downloaded chapters of a volume to an epub, like
+    /// [`generate_epub`](Manga::generate_epub), with the volume
+    /// in its title.
+    pub async fn generate_volume_epub(&mut self, volume: &Volume, output_path: &str) -> Result<(), DexError> {
+        let title = format!("{} - {}", &self.title, volume);
+        self.generate_titled_epub(&title, output_path).await
+    }
+
+    async fn generate_titled_epub(&mut self, title: &str, output_path: &str) -> Result<(), DexError> {
+        self.client.check_cancelled()?;
+        let mut book = Book::new()?;
         
-        book.add_author(&self.author_name);
-        book.add_title(&self.title);
-        book.add_css("assets/page.css", "Styles/page.css");
+        for creator in self.creators.iter() {
+            for role in creator.roles.iter() {
+                book.add_creator(&creator.name, role.marc_code())?;
+            }
+        }
+        book.add_title(title)?;
+        if let Some(language) = self.get_language() {
+            book.add_language(&language)?;
+        }
+        book.add_css("assets/page.css", "Styles/page.css")?;
 
-        let mut cover_image = self.fetch_cover().await;
-        book.add_cover_image(&mut cover_image);
+        let data = self.fetch_data().await?;
+        if let Some(description) = data.get_description() {
+            book.add_description(description)?;
+        }
+        for tag in data.get_tag_names() {
+            book.add_subject(tag)?;
+        }
+        let description = data.get_description().map(String::from);
+        let mut details = data.get_details();
+
+        // Crediting every group whose chapters are in the book
+        let mut groups: Vec<&str> = Vec::new();
+        for group in self.chapter_images.iter().flat_map(|ci| ci.groups.iter()) {
+            if !groups.contains(&group.name.as_str()) {
+                groups.push(&group.name);
+            }
+        }
+        for group in groups.iter() {
+            book.add_contributor(group, "trl")?;
+        }
+        if !groups.is_empty() {
+            details.push((String::from("Scanlation"), groups.join(", ")));
+        }
+        book.add_details_page(title, description.as_deref(), &details)?;
+
+        let mut cover_image = self.fetch_cover().await?;
+        book.add_cover_image(&mut cover_image)?;
 
         for (j, ci) in self.chapter_images.iter_mut().enumerate() {
             //book.add_chapter_partition(&ci.chapter_title, &format!("Text/Chapter_{}.xhtml", ci.chapter_no));
@@ -195,22 +503,21 @@ impl Manga {
             }
         }
 
-        book.generate(output_path)
-            .expect("Failed to generate epub");
+        self.client.check_cancelled()?;
+        let pages = book.page_count();
+        self.client.emit(Progress::ExportStarted { path: String::from(output_path), pages });
+        book.generate_with_progress(output_path, |written, pages| {
+            self.client.emit(Progress::ExportProgress { written, pages });
+        })?;
+        self.client.emit(Progress::ExportFinished { path: String::from(output_path) });
+        Ok(())
     }
 }
 
 impl From<&str> for Manga {
+    /// Creates a manga from its URL using a client with
+    /// the default settings.
     fn from(url: &str) -> Self {
-        let id = Manga::get_manga_id_from_url(url);
-        Manga { 
-            id,
-            url: String::from(url),
-            data: None,
-            title: String::from(""),
-            chapter_list: None,
-            chapter_images: Vec::new(),
-            author_name: String::from("")
-        }
+        Manga::with_client(url, DexClient::default())
     }
 }
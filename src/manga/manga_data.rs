@@ -1,5 +1,31 @@
 use serde::{Serialize, Deserialize};
 
+use std::collections::HashMap;
+
+use crate::util;
+use super::creator::CreatorRole;
+use super::relationship::Relationship;
+
+/// A text in several languages, keyed by language code
+pub type LocalizedString = HashMap<String, String>;
+
+/// Returns the English text of a localized string, or the one
+/// with the first language code in alphabetical order if there is
+/// no English text, so that the same text is always picked.
+fn any_language(text: &LocalizedString) -> Option<&str> {
+    text.get("en")
+        .or_else(|| text.iter().min_by_key(|(language, _)| *language).map(|(_, text)| text))
+        .map(String::as_str)
+}
+
+/// Returns the text of a localized string in the first of
+/// `languages` it has a text in.
+fn in_languages<'a, S: AsRef<str>>(text: &'a LocalizedString, languages: &[S]) -> Option<&'a str> {
+    languages.iter()
+        .find_map(|language| text.get(language.as_ref()))
+        .map(String::as_str)
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct MangaData {
     pub data: MangaDataInner,
@@ -7,43 +33,169 @@ pub struct MangaData {
 
 #[derive(Serialize, Deserialize)]
 pub struct MangaDataInner {
-    pub attributes: MangaTitle,
-    pub relationships: Vec<MangaRelation>,
+    pub attributes: MangaAttributes,
+    pub relationships: Vec<Relationship>,
 }
 
+/// The attributes of a manga.
 #[derive(Serialize, Deserialize)]
-pub struct MangaTitle {
-    pub title: MangaEnglishTitle,
+pub struct MangaAttributes {
+    #[serde(default, deserialize_with = "util::deserialize_localized")]
+    pub title: LocalizedString,
+    #[serde(default, rename = "altTitles", deserialize_with = "util::deserialize_null_default")]
+    pub alt_titles: Vec<LocalizedString>,
+    #[serde(default, deserialize_with = "util::deserialize_localized")]
+    pub description: LocalizedString,
+    #[serde(default, rename = "originalLanguage")]
+    pub original_language: String,
+    #[serde(default)]
+    pub status: Option<String>,
+    #[serde(default)]
+    pub year: Option<i32>,
+    #[serde(default, rename = "contentRating")]
+    pub content_rating: Option<String>,
+    #[serde(default, rename = "publicationDemographic")]
+    pub publication_demographic: Option<String>,
+    #[serde(default, deserialize_with = "util::deserialize_null_default")]
+    pub tags: Vec<Tag>,
+    #[serde(default, rename = "lastVolume")]
+    pub last_volume: Option<String>,
+    #[serde(default, rename = "lastChapter")]
+    pub last_chapter: Option<String>,
+    #[serde(default, rename = "availableTranslatedLanguages", deserialize_with = "util::deserialize_null_default")]
+    pub available_translated_languages: Vec<Option<String>>,
+    #[serde(default, deserialize_with = "util::deserialize_localized")]
+    pub links: HashMap<String, String>,
 }
 
+/// A tag of a manga, such as a genre or a theme.
 #[derive(Serialize, Deserialize)]
-pub struct MangaEnglishTitle {
-    pub en: String,
+pub struct Tag {
+    pub id: String,
+    pub attributes: TagAttributes,
 }
 
+/// The name of a tag and the group it belongs to, such as
+/// `genre`, `theme`, `format` or `content`.
 #[derive(Serialize, Deserialize)]
-pub struct MangaRelation {
-    id: String,
-    #[serde(rename = "type")]
-    relation_type: String,
+pub struct TagAttributes {
+    #[serde(default, deserialize_with = "util::deserialize_localized")]
+    pub name: LocalizedString,
+    #[serde(default)]
+    pub group: String,
+}
+
+impl Tag {
+    /// Returns the English name of the tag
+    pub fn get_name(&self) -> &str {
+        any_language(&self.attributes.name).unwrap_or("")
+    }
+
+    /// Returns the group of the tag
+    pub fn get_group(&self) -> &str {
+        &self.attributes.group
+    }
 }
 
 impl MangaData {
-    pub fn get_title(&self) -> &str {
-        &self.data.attributes.title.en
+    /// Returns the title of the manga in the first language of
+    /// `languages` it has one in.
+    ///
+    /// The main title is searched first, then the alternative
+    /// titles. Without a title in any of the languages, the main
+    /// title in whatever language it is in is returned, then any
+    /// alternative title.
+    pub fn get_title<S: AsRef<str>>(&self, languages: &[S]) -> &str {
+        let attributes = &self.data.attributes;
+        in_languages(&attributes.title, languages)
+            .or_else(|| attributes.alt_titles.iter().find_map(|titles| in_languages(titles, languages)))
+            .or_else(|| any_language(&attributes.title))
+            .or_else(|| attributes.alt_titles.iter().find_map(any_language))
+            .unwrap_or("")
     }
 
-    pub fn get_author_id(&self) -> String {
-        let relationships = &self.data.relationships;
-        let mut id = String::from("");
-        for relation in relationships.iter() {
-            if relation.relation_type == "author" {
-                id.push_str(&relation.id);
-                break;
+    /// Returns the attributes of the manga
+    pub fn get_attributes(&self) -> &MangaAttributes {
+        &self.data.attributes
+    }
+
+    /// Returns the English description, or the description in
+    /// any language if there is no English one
+    pub fn get_description(&self) -> Option<&str> {
+        any_language(&self.data.attributes.description)
+            .filter(|description| !description.is_empty())
+    }
+
+    /// Returns the names of the tags of the manga
+    pub fn get_tag_names(&self) -> Vec<&str> {
+        self.data.attributes.tags.iter()
+            .map(Tag::get_name)
+            .collect()
+    }
+
+    /// Returns the language the manga was originally published in
+    pub fn get_original_language(&self) -> Option<&str> {
+        Some(self.data.attributes.original_language.as_str())
+            .filter(|language| !language.is_empty())
+    }
+
+    /// Returns the languages the manga has been translated into
+    pub fn get_translated_languages(&self) -> Vec<&str> {
+        self.data.attributes.available_translated_languages.iter()
+            .flatten()
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Returns the details of the manga as labelled lines, in the
+    /// order they are shown in an epub. Empty details are left out.
+    pub fn get_details(&self) -> Vec<(String, String)> {
+        let attributes = &self.data.attributes;
+        let mut details: Vec<(String, String)> = Vec::new();
+        let mut push = |label: &str, value: String| {
+            if !value.is_empty() {
+                details.push((String::from(label), value));
             }
+        };
+
+        let alt_titles: Vec<&str> = attributes.alt_titles.iter()
+            .flat_map(|titles| titles.values())
+            .map(String::as_str)
+            .collect();
+        push("Alternative titles", alt_titles.join(", "));
+        push("Original language", attributes.original_language.clone());
+        push("Status", attributes.status.clone().unwrap_or_default());
+        push("Year", attributes.year.map(|year| year.to_string()).unwrap_or_default());
+        push("Demographic", attributes.publication_demographic.clone().unwrap_or_default());
+        push("Content rating", attributes.content_rating.clone().unwrap_or_default());
+
+        // One line per tag group, in the order the groups first appear
+        let mut groups: Vec<(&str, Vec<&str>)> = Vec::new();
+        for tag in attributes.tags.iter() {
+            match groups.iter_mut().find(|(group, _)| *group == tag.get_group()) {
+                Some((_, names)) => names.push(tag.get_name()),
+                None => groups.push((tag.get_group(), vec![tag.get_name()])),
+            }
+        }
+        for (group, names) in groups {
+            let mut label = String::from(group);
+            if let Some(first) = label.get_mut(..1) {
+                first.make_ascii_uppercase();
+            }
+            push(&label, names.join(", "));
         }
 
-        return id;
+        push("Last volume", attributes.last_volume.clone().unwrap_or_default());
+        push("Last chapter", attributes.last_chapter.clone().unwrap_or_default());
+        push("Translated into", self.get_translated_languages().join(", "));
+
+        let mut links: Vec<String> = attributes.links.iter()
+            .map(|(site, link)| format!("{}: {}", site, link))
+            .collect();
+        links.sort();
+        push("Links", links.join(", "));
+
+        details
     }
 
     pub fn get_cover_id(&self) -> String {
@@ -56,6 +208,23 @@ impl MangaData {
             }
         }
 
-        return id;
+        id
+    }
+
+    /// Returns the author and artist relationships of the manga,
+    /// in the order they were sent. A person who is both appears
+    /// once for each role.
+    pub fn get_creator_relationships(&self) -> Vec<&Relationship> {
+        self.data.relationships.iter()
+            .filter(|relation| CreatorRole::from_relation_type(&relation.relation_type).is_some())
+            .collect()
+    }
+
+    /// Returns the filename of the cover, if the cover was
+    /// included in the response
+    pub fn get_cover_file_name(&self) -> Option<&str> {
+        self.data.relationships.iter()
+            .find(|relation| relation.relation_type == "cover_art")
+            .and_then(Relationship::get_file_name)
     }
 }
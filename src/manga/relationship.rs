@@ -0,0 +1,39 @@
+use serde::{Serialize, Deserialize};
+
+/// A reference from a manga or chapter to a related entity, such
+/// as its author, cover or scanlation group.
+/// 
+/// The attributes are only present when the request asked for the
+/// reference to be expanded with `includes[]`.
+#[derive(Serialize, Deserialize)]
+pub struct Relationship {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub relation_type: String,
+    #[serde(default)]
+    pub attributes: Option<RelationshipAttributes>,
+}
+
+/// The attributes of an expanded relationship. Only the ones
+/// the crate uses are kept.
+#[derive(Default, Serialize, Deserialize)]
+pub struct RelationshipAttributes {
+    /// The name of an author, artist or scanlation group
+    #[serde(default)]
+    pub name: Option<String>,
+    /// The filename of a cover
+    #[serde(default, rename = "fileName")]
+    pub file_name: Option<String>,
+}
+
+impl Relationship {
+    /// Returns the name of an expanded author, artist or group
+    pub fn get_name(&self) -> Option<&str> {
+        self.attributes.as_ref()?.name.as_deref()
+    }
+
+    /// Returns the filename of an expanded cover
+    pub fn get_file_name(&self) -> Option<&str> {
+        self.attributes.as_ref()?.file_name.as_deref()
+    }
+}
@@ -0,0 +1,52 @@
+use super::chapter::{DuplicateStrategy, LanguageMode, UnnumberedPlacement};
+
+/// Which chapters of a manga are kept, and in which language, once
+/// its chapter list is fetched.
+///
+/// # Examples
+/// ```
+/// use dexloader::manga::{DuplicateStrategy, Manga, SelectionOptions};
+///
+/// let selection = SelectionOptions {
+///     languages: vec![String::from("es-la"), String::from("en")],
+///     duplicates: DuplicateStrategy::MostPages,
+///     ..SelectionOptions::default()
+/// };
+/// let manga = Manga::from("259dfd8a-f06a-4825-8fa6-a2dcd7274230").with_selection(selection);
+/// ```
+#[derive(Clone, Debug)]
+pub struct SelectionOptions {
+    /// The languages the title of a manga is looked up in, in
+    /// order of preference
+    pub title_languages: Vec<String>,
+    /// Scanlation groups, by id or name, whose uploads come first
+    /// among the uploads of the same chapter, in order of preference
+    pub preferred_groups: Vec<String>,
+    /// Scanlation groups, by id or name, whose chapters are skipped
+    pub blocked_groups: Vec<String>,
+    /// How one upload is picked when a chapter was uploaded
+    /// several times
+    pub duplicates: DuplicateStrategy,
+    /// Where unnumbered chapters, such as oneshots, are placed
+    pub unnumbered: UnnumberedPlacement,
+    /// The languages chapters are requested in, in order of
+    /// preference
+    pub languages: Vec<String>,
+    /// Whether chapters are requested in the preferred languages,
+    /// in the original language of the manga, or in every language
+    pub language_mode: LanguageMode,
+}
+
+impl Default for SelectionOptions {
+    fn default() -> Self {
+        SelectionOptions {
+            title_languages: vec![String::from("en"), String::from("ja-ro"), String::from("ja")],
+            preferred_groups: Vec::new(),
+            blocked_groups: Vec::new(),
+            duplicates: DuplicateStrategy::default(),
+            unnumbered: UnnumberedPlacement::default(),
+            languages: vec![String::from("en")],
+            language_mode: LanguageMode::default(),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! This module reports the progress of downloads and exports.
+//!
+//! A [`DexClient`](crate::client::DexClient) given a
+//! [`ProgressListener`] with
+//! [`with_progress`](crate::client::DexClient::with_progress) hands
+//! it a [`Progress`] event at every step. Closures and the sending
+//! half of a tokio channel are listeners, so events can be handled
+//! in place or consumed elsewhere, such as by a user interface.
+extern crate tokio;
+
+use tokio::sync::mpsc;
+
+use std::time::Duration;
+
+use crate::manga::ChapterNumber;
+
+/// A step in downloading a manga or exporting it.
+#[derive(Clone, Debug)]
+pub enum Progress {
+    /// The chapter list was fetched, sorted and filtered
+    ChapterListFetched {
+        /// The chapters kept in the list
+        count: usize,
+        /// The chapters the API has for the manga in the languages
+        /// asked for
+        total: i32,
+    },
+    /// Chapters of the list are about to be downloaded
+    ChaptersScheduled {
+        /// The chapters about to be downloaded
+        count: usize,
+    },
+    /// A chapter started downloading
+    ChapterStarted {
+        chapter_id: String,
+        chapter_no: ChapterNumber,
+        pages: usize,
+    },
+    /// A page or cover was downloaded
+    PageDownloaded {
+        url: String,
+        page_no: i32,
+        bytes: usize,
+        /// Whether the MangaDex@Home server had the page cached
+        cached: bool,
+    },
+    /// A page was taken from the page cache instead of downloaded
+    PageReused {
+        chapter_id: String,
+        page_no: i32,
+    },
+    /// A page failed and is about to be tried again
+    Retry {
+        url: String,
+        page_no: i32,
+        attempt: u32,
+        delay: Duration,
+        error: String,
+    },
+    /// Every page of a chapter is downloaded
+    ChapterFinished {
+        chapter_id: String,
+    },
+    /// An epub started being written
+    ExportStarted {
+        path: String,
+        pages: usize,
+    },
+    /// A page was added to the epub being written
+    ExportProgress {
+        written: usize,
+        pages: usize,
+    },
+    /// An epub was written
+    ExportFinished {
+        path: String,
+    },
+}
+
+/// Receives the progress events of a client.
+///
+/// Events are delivered on the task doing the work, so listeners
+/// should return quickly.
+///
+/// # Examples
+/// ```
+/// use dexloader::client::DexClient;
+/// use dexloader::progress::Progress;
+///
+/// let client = DexClient::default().with_progress(|event: Progress| {
+///     if let Progress::ChapterFinished { chapter_id } = event {
+///         println!("Finished {}", chapter_id);
+///     }
+/// });
+/// ```
+pub trait ProgressListener: Send + Sync {
+    fn on_progress(&self, event: Progress);
+}
+
+impl<F> ProgressListener for F
+    where F: Fn(Progress) + Send + Sync
+{
+    fn on_progress(&self, event: Progress) {
+        self(event)
+    }
+}
+
+impl ProgressListener for mpsc::UnboundedSender<Progress> {
+    /// Sends the event, dropping it if the receiver is gone
+    fn on_progress(&self, event: Progress) {
+        let _ = self.send(event);
+    }
+}
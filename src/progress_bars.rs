@@ -0,0 +1,104 @@
+//! Renders the progress events of the client as progress bars.
+//!
+//! The first bar counts the chapters and estimates the time left,
+//! the second one follows the pages of the current chapter or epub
+//! along with the download throughput.
+extern crate indicatif;
+
+use indicatif::{HumanBytes, MultiProgress, ProgressBar, ProgressStyle};
+
+use std::sync::Mutex;
+use std::time::Instant;
+
+use dexloader::progress::Progress;
+
+/// Bytes downloaded since the first page
+struct Throughput {
+    bytes: u64,
+    started: Option<Instant>,
+}
+
+impl Throughput {
+    fn describe(&self) -> String {
+        let secs = self.started.map_or(0.0, |started| started.elapsed().as_secs_f64());
+        let rate = if secs > 0.0 { (self.bytes as f64 / secs) as u64 } else { 0 };
+        format!("{} at {}/s", HumanBytes(self.bytes), HumanBytes(rate))
+    }
+}
+
+pub struct ProgressBars {
+    multi: MultiProgress,
+    chapters: ProgressBar,
+    pages: ProgressBar,
+    throughput: Mutex<Throughput>,
+}
+
+impl ProgressBars {
+    pub fn new() -> Self {
+        let multi = MultiProgress::new();
+        let chapters = multi.add(ProgressBar::new(0));
+        chapters.set_style(
+            ProgressStyle::with_template("{prefix:>8} [{wide_bar}] {pos}/{len} ETA {eta}")
+                .unwrap()
+                .progress_chars("=> ")
+        );
+        chapters.set_prefix("Chapters");
+
+        let pages = multi.add(ProgressBar::new(0));
+        pages.set_style(
+            ProgressStyle::with_template("{prefix:>8} [{wide_bar}] {pos}/{len} {msg}")
+                .unwrap()
+                .progress_chars("=> ")
+        );
+
+        ProgressBars {
+            multi,
+            chapters,
+            pages,
+            throughput: Mutex::new(Throughput { bytes: 0, started: None }),
+        }
+    }
+
+    /// Updates the bars with an event of the client
+    pub fn handle(&self, event: Progress) {
+        match event {
+            Progress::ChapterListFetched { .. } => {},
+            Progress::ChaptersScheduled { count } => self.chapters.inc_length(count as u64),
+            Progress::ChapterStarted { chapter_no, pages, .. } => {
+                self.pages.reset();
+                self.pages.set_length(pages as u64);
+                self.pages.set_prefix(format!("Ch. {}", chapter_no));
+            },
+            Progress::PageDownloaded { bytes, .. } => {
+                let mut throughput = self.throughput.lock().unwrap();
+                throughput.started.get_or_insert_with(Instant::now);
+                throughput.bytes += bytes as u64;
+                self.pages.set_message(throughput.describe());
+                if self.pages.position() < self.pages.length().unwrap_or(0) {
+                    self.pages.inc(1);
+                }
+            },
+            Progress::PageReused { .. } => self.pages.inc(1),
+            Progress::Retry { page_no, attempt, .. } => {
+                self.pages.set_message(format!("retrying page {} ({})", page_no + 1, attempt));
+            },
+            Progress::ChapterFinished { .. } => self.chapters.inc(1),
+            Progress::ExportStarted { pages, .. } => {
+                self.pages.reset();
+                self.pages.set_length(pages as u64);
+                self.pages.set_prefix("Export");
+                self.pages.set_message("");
+            },
+            Progress::ExportProgress { written, .. } => self.pages.set_position(written as u64),
+            Progress::ExportFinished { path } => {
+                let _ = self.multi.println(format!("Saved {}", path));
+            },
+        }
+    }
+
+    /// Removes the bars once everything is done
+    pub fn finish(&self) {
+        self.pages.finish_and_clear();
+        self.chapters.finish();
+    }
+}
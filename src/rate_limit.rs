@@ -0,0 +1,194 @@
+//! This module keeps the crate within the MangaDex API rate limits.
+//!
+//! Requests to the API take a token from a bucket shared by every
+//! clone of a [`DexClient`](crate::client::DexClient). Requests to
+//! `/at-home/server` additionally take a token from their own,
+//! stricter bucket. When the API reports that the limit is reached,
+//! the bucket is paused and callers wait instead of failing.
+extern crate reqwest;
+
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use log::warn;
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// A number of requests allowed within a window of time.
+#[derive(Clone, Debug)]
+pub struct Quota {
+    pub requests: u32,
+    pub per: Duration,
+}
+
+/// The quotas of every endpoint class.
+#[derive(Clone, Debug)]
+pub struct RateLimits {
+    /// Shared by every request to the API
+    pub api: Quota,
+    /// Requests to `/at-home/server/{id}`
+    pub at_home: Quota,
+}
+
+impl Default for RateLimits {
+    fn default() -> Self {
+        RateLimits {
+            api: Quota { requests: 5, per: Duration::from_secs(1) },
+            at_home: Quota { requests: 40, per: Duration::from_secs(60) },
+        }
+    }
+}
+
+/// The classes of endpoints that are limited separately.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EndpointClass {
+    Api,
+    AtHome,
+}
+
+impl EndpointClass {
+    /// Classifies a request by the path of its URL
+    pub fn from_path(path: &str) -> Self {
+        if path.contains("/at-home/server/") {
+            EndpointClass::AtHome
+        } else {
+            EndpointClass::Api
+        }
+    }
+}
+
+struct Bucket {
+    capacity: f64,
+    tokens: f64,
+    refill_per_sec: f64,
+    last_refill: Instant,
+    paused_until: Option<Instant>,
+}
+
+impl Bucket {
+    fn new(quota: &Quota) -> Self {
+        let capacity = quota.requests.max(1) as f64;
+        Bucket {
+            capacity,
+            tokens: capacity,
+            refill_per_sec: capacity / quota.per.as_secs_f64().max(f64::EPSILON),
+            last_refill: Instant::now(),
+            paused_until: None,
+        }
+    }
+
+    /// Takes a token, or returns how long to wait before trying again
+    fn try_take(&mut self, now: Instant) -> Result<(), Duration> {
+        if let Some(until) = self.paused_until {
+            if until > now {
+                return Err(until - now);
+            }
+            self.paused_until = None;
+        }
+
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.last_refill = now;
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / self.refill_per_sec))
+        }
+    }
+
+    fn pause(&mut self, until: Instant) {
+        self.tokens = 0.0;
+        self.paused_until = Some(self.paused_until.map_or(until, |u| u.max(until)));
+    }
+}
+
+/// A token bucket rate limiter per endpoint class.
+pub struct RateLimiter {
+    api: Mutex<Bucket>,
+    at_home: Mutex<Bucket>,
+}
+
+impl RateLimiter {
+    /// Creates a limiter with full buckets
+    pub fn new(limits: &RateLimits) -> Self {
+        RateLimiter {
+            api: Mutex::new(Bucket::new(&limits.api)),
+            at_home: Mutex::new(Bucket::new(&limits.at_home)),
+        }
+    }
+
+    fn bucket(&self, class: EndpointClass) -> &Mutex<Bucket> {
+        match class {
+            EndpointClass::Api => &self.api,
+            EndpointClass::AtHome => &self.at_home,
+        }
+    }
+
+    /// Waits until a request of the given class may be made.
+    pub async fn acquire(&self, class: EndpointClass) {
+        if class == EndpointClass::AtHome {
+            Self::take(&self.at_home).await;
+        }
+        Self::take(&self.api).await;
+    }
+
+    async fn take(bucket: &Mutex<Bucket>) {
+        loop {
+            let wait = bucket.lock().unwrap().try_take(Instant::now());
+            match wait {
+                Ok(()) => return,
+                Err(delay) => tokio::time::sleep(delay).await,
+            }
+        }
+    }
+
+    /// Reads the rate limit headers of a response and pauses the
+    /// bucket of its class if the API asks to back off.
+    ///
+    /// Returns how long the bucket was paused for, if at all.
+    pub fn observe(&self, class: EndpointClass, status: StatusCode, headers: &HeaderMap) -> Option<Duration> {
+        let header = |name: &str| -> Option<&str> {
+            headers.get(name).and_then(|v| v.to_str().ok())
+        };
+
+        let exhausted = header("x-ratelimit-remaining")
+            .and_then(|v| v.trim().parse::<i64>().ok())
+            .is_some_and(|remaining| remaining <= 0);
+
+        // X-RateLimit-Retry-After is the unix time the window resets at
+        let reset = header("x-ratelimit-retry-after")
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .map(|at| {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+                Duration::from_secs(at.saturating_sub(now))
+            });
+
+        // Retry-After is a number of seconds to wait
+        let retry_after = header("retry-after")
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .map(Duration::from_secs);
+
+        // The reset time is sent with every response, but only
+        // matters once the window is used up
+        let limited = exhausted || status == StatusCode::TOO_MANY_REQUESTS;
+        let delay = match (retry_after, reset) {
+            (Some(after), Some(reset)) if limited => after.max(reset),
+            (Some(after), _) => after,
+            (None, Some(reset)) if limited => reset,
+            (None, None) if limited => Duration::from_secs(1),
+            _ => return None,
+        };
+
+        warn!("Rate limited on {:?} endpoints, pausing for {:?}", class, delay);
+        self.bucket(class)
+            .lock()
+            .unwrap()
+            .pause(Instant::now() + delay);
+        Some(delay)
+    }
+}
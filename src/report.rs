@@ -0,0 +1,146 @@
+//! This module reports the outcome of page downloads back to the
+//! MangaDex@Home network in the background.
+//!
+//! Downloads hand their reports to a [`Reporter`] through a channel
+//! and carry on. A background task drains the channel in batches and
+//! sends the reports to `/report`. Reporting never blocks or fails a
+//! download: reports that cannot be queued are dropped, and reports
+//! the network rejects are only counted in [`ReportStats`].
+extern crate reqwest;
+extern crate tokio;
+
+use futures::future::join_all;
+use log::{debug, warn};
+use serde::{Serialize, Deserialize};
+use tokio::sync::{mpsc, oneshot};
+
+use std::sync::{Arc, Mutex};
+
+/// The most reports waiting to be sent before new ones are dropped
+const QUEUE_SIZE: usize = 1024;
+
+/// The most reports sent at the same time
+const BATCH_SIZE: usize = 16;
+
+/// The body of a report POST request
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Report {
+    pub url: String,
+    pub success: bool,
+    pub cached: bool,
+    pub bytes: usize,
+    pub duration: u128,
+}
+
+/// Counts of what happened to the reports of a client.
+#[derive(Clone, Debug, Default)]
+pub struct ReportStats {
+    /// Reports the network accepted
+    pub sent: usize,
+    /// Reports that could not be delivered
+    pub failed: usize,
+    /// Reports dropped because the queue was full
+    pub dropped: usize,
+    /// The reason the last failed report was not delivered
+    pub last_error: Option<String>,
+}
+
+enum Message {
+    Report(Report),
+    Flush(oneshot::Sender<()>),
+}
+
+/// A handle to the background reporting task.
+pub struct Reporter {
+    sender: mpsc::Sender<Message>,
+    stats: Arc<Mutex<ReportStats>>,
+}
+
+impl Reporter {
+    /// Starts the background task sending reports to `report_url`.
+    ///
+    /// Must be called from within a tokio runtime.
+    pub fn spawn(http: reqwest::Client, report_url: String) -> Self {
+        let (sender, receiver) = mpsc::channel(QUEUE_SIZE);
+        let stats = Arc::new(Mutex::new(ReportStats::default()));
+
+        tokio::spawn(run(http, report_url, receiver, stats.clone()));
+        Reporter { sender, stats }
+    }
+
+    /// Queues a report without waiting. The report is dropped if
+    /// the queue is full.
+    pub fn send(&self, report: Report) {
+        if self.sender.try_send(Message::Report(report)).is_err() {
+            self.stats.lock().unwrap().dropped += 1;
+        }
+    }
+
+    /// Waits until every report queued so far has been sent.
+    pub async fn flush(&self) {
+        let (done, wait) = oneshot::channel();
+        if self.sender.send(Message::Flush(done)).await.is_ok() {
+            let _ = wait.await;
+        }
+    }
+
+    /// Returns what happened to the reports so far
+    pub fn stats(&self) -> ReportStats {
+        self.stats.lock().unwrap().clone()
+    }
+}
+
+async fn run(http: reqwest::Client, report_url: String, mut receiver: mpsc::Receiver<Message>, stats: Arc<Mutex<ReportStats>>) {
+    while let Some(message) = receiver.recv().await {
+        // Draining whatever else is already waiting into one batch
+        let mut batch = Vec::new();
+        let mut flushes = Vec::new();
+        let mut next = Some(message);
+        while let Some(message) = next {
+            match message {
+                Message::Report(report) => batch.push(report),
+                Message::Flush(done) => flushes.push(done),
+            }
+            next = if batch.len() < BATCH_SIZE { receiver.try_recv().ok() } else { None };
+        }
+
+        let results = join_all(batch.iter().map(|report| send_report(&http, &report_url, report)))
+            .await;
+
+        {
+            let mut stats = stats.lock().unwrap();
+            for result in results {
+                match result {
+                    Ok(()) => stats.sent += 1,
+                    Err(e) => {
+                        stats.failed += 1;
+                        stats.last_error = Some(e);
+                    },
+                }
+            }
+        }
+
+        for done in flushes {
+            let _ = done.send(());
+        }
+    }
+}
+
+async fn send_report(http: &reqwest::Client, report_url: &str, report: &Report) -> Result<(), String> {
+    let result = http.post(report_url)
+        .json(report)
+        .send()
+        .await;
+
+    let error = match result {
+        Ok(res) if res.status().is_success() => {
+            debug!("Reported {} (success: {})", &report.url, report.success);
+            return Ok(());
+        },
+        Ok(res) => format!("{} returned {}", report_url, res.status()),
+        Err(e) => e.to_string(),
+    };
+
+    warn!("Failed to report {}: {}", &report.url, error);
+    Err(error)
+}
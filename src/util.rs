@@ -4,12 +4,17 @@
 extern crate serde;
 
 use serde::{de::Error, Deserialize, Deserializer};
+use std::collections::HashMap;
 use std::str::FromStr;
 
 /// A function to help serde parse a string as f32.
 /// 
 /// # Examples
 /// ```
+/// use serde::Deserialize;
+/// use dexloader::util::deserialize_to_f32;
+/// 
+/// #[derive(Deserialize)]
 /// struct SomeStruct {
 ///     #[serde(deserialize_with = "deserialize_to_f32")]
 ///     float_inside_a_string: f32,
@@ -27,6 +32,10 @@ pub fn deserialize_to_f32<'de, D>(deserializer: D) -> Result<f32, D::Error>
 /// 
 /// # Examples
 /// ```
+/// use serde::Deserialize;
+/// use dexloader::util::deserialize_to_option_f32;
+/// 
+/// #[derive(Deserialize)]
 /// struct SomeStruct {
 ///     #[serde(deserialize_with = "deserialize_to_option_f32")]
 ///     float_inside_a_string: Option<f32>,
@@ -52,6 +61,10 @@ pub fn deserialize_to_option_f32<'de, D>(deserializer: D) -> Result<Option<f32>,
 /// 
 /// # Examples
 /// ```
+/// use serde::Deserialize;
+/// use dexloader::util::deserialize_title;
+/// 
+/// #[derive(Deserialize)]
 /// struct SomeStruct {
 ///     #[serde(deserialize_with = "deserialize_title")]
 ///     title: String,
@@ -66,3 +79,98 @@ pub fn deserialize_title<'de, D> (deserializer: D) -> Result<String, D::Error>
             x.unwrap_or("".to_string())
         })
 }
+
+/// A function to help serde parse a map of localized strings,
+/// such as a description. MangaDex sends an empty list or null
+/// instead of an empty map, which are both read as an empty map.
+/// 
+/// # Examples
+/// ```
+/// use std::collections::HashMap;
+/// use serde::Deserialize;
+/// use dexloader::util::deserialize_localized;
+/// 
+/// #[derive(Deserialize)]
+/// struct SomeStruct {
+///     #[serde(default, deserialize_with = "deserialize_localized")]
+///     description: HashMap<String, String>,
+/// }
+/// 
+/// let parsed: SomeStruct = serde_json::from_str(r#"{"description": []}"#).unwrap();
+/// assert!(parsed.description.is_empty());
+/// ```
+pub fn deserialize_localized<'de, D>(deserializer: D) -> Result<HashMap<String, String>, D::Error>
+    where
+        D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Localized {
+        Map(HashMap<String, Option<String>>),
+        Other(#[allow(dead_code)] serde::de::IgnoredAny),
+    }
+
+    let localized: Localized = Deserialize::deserialize(deserializer)?;
+    Ok(match localized {
+        Localized::Map(map) => map.into_iter()
+            .filter_map(|(language, text)| text.map(|text| (language, text)))
+            .collect(),
+        Localized::Other(_) => HashMap::new(),
+    })
+}
+
+/// A function to help serde read null as the default value
+/// of a type, such as an empty list.
+/// 
+/// # Examples
+/// ```
+/// use serde::Deserialize;
+/// use dexloader::util::deserialize_null_default;
+/// 
+/// #[derive(Deserialize)]
+/// struct SomeStruct {
+///     #[serde(default, deserialize_with = "deserialize_null_default")]
+///     languages: Vec<String>,
+/// }
+/// 
+/// let parsed: SomeStruct = serde_json::from_str(r#"{"languages": null}"#).unwrap();
+/// assert!(parsed.languages.is_empty());
+/// ```
+pub fn deserialize_null_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+        T: Default + Deserialize<'de>,
+{
+    let value: Option<T> = Deserialize::deserialize(deserializer)?;
+    Ok(value.unwrap_or_default())
+}
+
+/// Turns a text, such as the title of a manga, into a name that
+/// can be used for a file on any platform.
+/// 
+/// Path separators and the other characters that are not allowed
+/// in filenames are replaced with underscores.
+/// 
+/// # Examples
+/// ```
+/// use dexloader::util::sanitize_file_name;
+/// 
+/// assert_eq!(sanitize_file_name("Fate/Zero: Vol. 1?"), "Fate_Zero_ Vol. 1_");
+/// assert_eq!(sanitize_file_name(" .. "), "manga");
+/// ```
+pub fn sanitize_file_name(name: &str) -> String {
+    let sanitized: String = name.chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let sanitized = sanitized.trim_matches(|c: char| c == '.' || c.is_whitespace());
+
+    if sanitized.is_empty() {
+        String::from("manga")
+    } else {
+        String::from(sanitized)
+    }
+}
@@ -0,0 +1,121 @@
+mod common;
+
+use std::path::Path;
+
+use dexloader::client::{ClientConfig, DexClient};
+use dexloader::error::DexError;
+use dexloader::manga::Manga;
+
+use common::{Fault, MockServer, MANGA_URL};
+
+fn cached_client(server: &MockServer, cache_dir: &Path) -> DexClient {
+    DexClient::new(ClientConfig {
+        cache_dir: Some(cache_dir.to_path_buf()),
+        ..server.config()
+    }).unwrap()
+}
+
+#[tokio::test]
+async fn skips_chapters_completed_by_an_earlier_run() {
+    let cache = tempfile::tempdir().unwrap();
+
+    let server = MockServer::start().await;
+    let mut manga = Manga::with_client(MANGA_URL, cached_client(&server, cache.path()));
+    manga.get_chapters(None, 0, None).await.unwrap();
+    manga.download_chapters(true, false).await.unwrap();
+    assert_eq!(server.count("/data/"), 5);
+
+    let server = MockServer::start().await;
+    let mut manga = Manga::with_client(MANGA_URL, cached_client(&server, cache.path()));
+    manga.get_chapters(None, 0, None).await.unwrap();
+    manga.download_chapters(true, false).await.unwrap();
+
+    let pages: Vec<usize> = manga.chapter_images.iter().map(|c| c.images.len()).collect();
+    assert_eq!(pages, vec![3, 2]);
+    assert_eq!(server.count("/at-home/server/"), 0);
+    assert_eq!(server.count("/data/"), 0);
+}
+
+#[tokio::test]
+async fn resumes_an_interrupted_chapter() {
+    let cache = tempfile::tempdir().unwrap();
+    let last_page = "/data/3f6a0c3bd5f4b2a1e9c8d7f6a5b4c3d2/3-";
+
+    let server = MockServer::start().await;
+    server.inject_times(last_page, Fault::Status(500), 100);
+    let mut manga = Manga::with_client(MANGA_URL, cached_client(&server, cache.path()));
+    manga.get_chapters(Some(1), 0, None).await.unwrap();
+    assert!(manga.download_chapters(true, false).await.is_err());
+
+    let server = MockServer::start().await;
+    let mut manga = Manga::with_client(MANGA_URL, cached_client(&server, cache.path()));
+    manga.get_chapters(Some(1), 0, None).await.unwrap();
+    manga.download_chapters(true, false).await.unwrap();
+
+    let pages: Vec<i32> = manga.chapter_images[0].images.iter().map(|i| i.page_no).collect();
+    assert_eq!(pages, vec![0, 1, 2]);
+    assert_eq!(server.count("/data/"), 1);
+    assert_eq!(server.count(last_page), 1);
+}
+
+#[tokio::test]
+async fn reuses_cached_responses_and_covers() {
+    let cache = tempfile::tempdir().unwrap();
+    let out = tempfile::tempdir().unwrap();
+    let server = MockServer::start().await;
+
+    for i in 0..2 {
+        let mut manga = Manga::with_client(MANGA_URL, cached_client(&server, cache.path()));
+        manga.get_chapters(Some(1), 0, None).await.unwrap();
+        manga.download_chapters(true, false).await.unwrap();
+        let path = out.path().join(format!("{}.epub", i));
+        manga.generate_epub(path.to_str().unwrap()).await.unwrap();
+    }
+
+    assert_eq!(server.count(&common::manga_path(common::MANGA_ID)), 1);
+    assert_eq!(server.count("/covers/"), 1);
+    assert_eq!(server.count(&common::feed_path(common::MANGA_ID)), 1);
+}
+
+#[tokio::test]
+async fn builds_epubs_offline_from_the_cache() {
+    let cache = tempfile::tempdir().unwrap();
+    let out = tempfile::tempdir().unwrap();
+    let server = MockServer::start().await;
+
+    let mut manga = Manga::with_client(MANGA_URL, cached_client(&server, cache.path()));
+    manga.get_chapters(None, 0, None).await.unwrap();
+    manga.download_chapters(true, false).await.unwrap();
+    manga.generate_epub(out.path().join("online.epub").to_str().unwrap()).await.unwrap();
+    let requests = server.requests().len();
+
+    let client = DexClient::new(ClientConfig {
+        cache_dir: Some(cache.path().to_path_buf()),
+        offline: true,
+        ..server.config()
+    }).unwrap();
+    let mut manga = Manga::with_client(MANGA_URL, client);
+    manga.get_chapters(None, 0, None).await.unwrap();
+    manga.download_chapters(true, false).await.unwrap();
+    manga.generate_epub(out.path().join("offline.epub").to_str().unwrap()).await.unwrap();
+
+    assert_eq!(server.requests().len(), requests);
+    let pages: Vec<usize> = manga.chapter_images.iter().map(|c| c.images.len()).collect();
+    assert_eq!(pages, vec![3, 2]);
+}
+
+#[tokio::test]
+async fn fails_offline_when_nothing_is_cached() {
+    let cache = tempfile::tempdir().unwrap();
+    let server = MockServer::start().await;
+    let client = DexClient::new(ClientConfig {
+        cache_dir: Some(cache.path().to_path_buf()),
+        offline: true,
+        ..server.config()
+    }).unwrap();
+
+    let mut manga = Manga::with_client(MANGA_URL, client);
+    let err = manga.get_chapters(None, 0, None).await.unwrap_err();
+    assert!(matches!(err, DexError::Offline(_)));
+    assert!(server.requests().is_empty());
+}
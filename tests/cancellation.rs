@@ -0,0 +1,72 @@
+mod common;
+
+use std::time::Duration;
+
+use dexloader::client::{ClientConfig, DexClient};
+use dexloader::error::DexError;
+use dexloader::manga::Manga;
+
+use common::{Fault, MockServer, MANGA_URL};
+
+#[tokio::test]
+async fn cancelled_clients_make_no_requests() {
+    let server = MockServer::start().await;
+    let client = server.client();
+    client.cancellation().cancel();
+
+    let mut manga = Manga::with_client(MANGA_URL, client);
+    assert!(matches!(manga.get_chapters(None, 0, None).await, Err(DexError::Cancelled)));
+    assert!(server.requests().is_empty());
+}
+
+#[tokio::test]
+async fn finishes_pages_in_flight_and_resumes_later() {
+    let cache = tempfile::tempdir().unwrap();
+    let config = |server: &MockServer| ClientConfig {
+        cache_dir: Some(cache.path().to_path_buf()),
+        concurrent_downloads: 1,
+        ..server.config()
+    };
+
+    let server = MockServer::start().await;
+    server.inject("/data/", Fault::Delay(Duration::from_millis(300)));
+    let client = DexClient::new(config(&server)).unwrap();
+    let mut manga = Manga::with_client(MANGA_URL, client.clone());
+    manga.get_chapters(Some(1), 0, None).await.unwrap();
+
+    let token = client.cancellation().clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        token.cancel();
+    });
+    assert!(matches!(manga.download_chapters(true, false).await, Err(DexError::Cancelled)));
+    assert_eq!(server.count("/data/"), 1);
+
+    let server = MockServer::start().await;
+    let mut manga = Manga::with_client(MANGA_URL, DexClient::new(config(&server)).unwrap());
+    manga.get_chapters(Some(1), 0, None).await.unwrap();
+    manga.download_chapters(true, false).await.unwrap();
+
+    assert_eq!(manga.chapter_images[0].images.len(), 3);
+    assert_eq!(server.count("/data/"), 2);
+}
+
+#[tokio::test]
+async fn epubs_are_written_atomically() {
+    let server = MockServer::start().await;
+    let out = tempfile::tempdir().unwrap();
+    let path = out.path().join("out.epub");
+
+    let mut manga = Manga::with_client(MANGA_URL, server.client());
+    manga.get_chapters(Some(1), 0, None).await.unwrap();
+    manga.download_chapters(true, false).await.unwrap();
+    manga.generate_epub(path.to_str().unwrap()).await.unwrap();
+
+    assert!(path.is_file());
+    assert!(!out.path().join("out.epub.part").exists());
+
+    manga.get_client().cancellation().cancel();
+    let other = out.path().join("other.epub");
+    assert!(matches!(manga.generate_epub(other.to_str().unwrap()).await, Err(DexError::Cancelled)));
+    assert!(!other.exists());
+}
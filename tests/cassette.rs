@@ -0,0 +1,87 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use dexloader::cassette::CassetteMode;
+use dexloader::client::{ClientConfig, DexClient};
+use dexloader::error::DexError;
+use dexloader::manga::Manga;
+use dexloader::rate_limit::Quota;
+
+use common::{Fault, MockServer, MANGA_URL};
+
+#[tokio::test]
+async fn replays_a_recorded_session_without_the_network() {
+    let dir = tempfile::tempdir().unwrap();
+    let cassette = dir.path().join("session.jsonl");
+    let server = MockServer::start().await;
+
+    let client = DexClient::new(ClientConfig {
+        cassette: Some(CassetteMode::Record(cassette.clone())),
+        ..server.config()
+    }).unwrap();
+    let mut manga = Manga::with_client(MANGA_URL, client);
+    manga.get_chapters(None, 0, None).await.unwrap();
+    manga.download_chapters(true, true).await.unwrap();
+    manga.get_client().flush_reports().await;
+    let requests = server.requests().len();
+
+    let client = DexClient::new(ClientConfig {
+        cassette: Some(CassetteMode::Replay(cassette)),
+        ..server.config()
+    }).unwrap();
+    let mut manga = Manga::with_client(MANGA_URL, client);
+    manga.get_chapters(None, 0, None).await.unwrap();
+    manga.download_chapters(true, true).await.unwrap();
+    manga.get_client().flush_reports().await;
+
+    assert_eq!(server.requests().len(), requests);
+    assert_eq!(manga.title, "Yofukashi no Uta");
+    let pages: Vec<usize> = manga.chapter_images.iter().map(|c| c.images.len()).collect();
+    assert_eq!(pages, vec![3, 2]);
+}
+
+#[tokio::test]
+async fn requests_missing_from_the_cassette_fail() {
+    let dir = tempfile::tempdir().unwrap();
+    let cassette = dir.path().join("empty.jsonl");
+    std::fs::write(&cassette, "").unwrap();
+    let server = MockServer::start().await;
+
+    let client = DexClient::new(ClientConfig {
+        cassette: Some(CassetteMode::Replay(cassette)),
+        ..server.config()
+    }).unwrap();
+    let mut manga = Manga::with_client(MANGA_URL, client);
+
+    assert!(matches!(manga.fetch_data().await, Err(DexError::NotRecorded(_))));
+    assert!(server.requests().is_empty());
+}
+
+#[tokio::test]
+async fn replays_are_not_rate_limited() {
+    let dir = tempfile::tempdir().unwrap();
+    let cassette = dir.path().join("limited.jsonl");
+    let server = MockServer::start().await;
+    server.inject(&common::feed_path(common::MANGA_ID), Fault::RateLimited(1));
+
+    let config = |mode: CassetteMode| {
+        let mut config = server.config();
+        config.cassette = Some(mode);
+        config.rate_limits.api = Quota { requests: 1, per: Duration::from_millis(500) };
+        DexClient::new(config).unwrap()
+    };
+    let mut manga = Manga::with_client(MANGA_URL, config(CassetteMode::Record(cassette.clone())));
+    for _ in 0..3 {
+        manga.get_chapters(Some(1), 0, None).await.unwrap();
+    }
+
+    let start = Instant::now();
+    let mut manga = Manga::with_client(MANGA_URL, config(CassetteMode::Replay(cassette)));
+    for _ in 0..3 {
+        manga.get_chapters(Some(1), 0, None).await.unwrap();
+    }
+
+    assert!(start.elapsed() < Duration::from_millis(500));
+    assert_eq!(manga.chapter_list.unwrap().data.len(), 1);
+}
@@ -0,0 +1,55 @@
+mod common;
+
+use dexloader::manga::{ChapterList, ChapterNumber, UnnumberedPlacement};
+use serde_json::json;
+
+use common::chapter_list;
+
+/// A chapter list with chapters of the given numbers
+fn numbered(numbers: &[Option<&str>]) -> ChapterList {
+    let chapters: Vec<_> = numbers.iter().map(|number| json!({ "chapter": number })).collect();
+    chapter_list(&chapters)
+}
+
+fn numbers(list: &ChapterList) -> Vec<String> {
+    list.data.iter().map(|chapter| chapter.get_chapter_number().to_string()).collect()
+}
+
+#[test]
+fn numbers_are_ordered_naturally() {
+    let mut numbers: Vec<ChapterNumber> = ["10", "2", "10.5", "10a", "1.25", "1.3", "Extra", "010.50", "9"]
+        .iter()
+        .map(|number| ChapterNumber::from(*number))
+        .collect();
+    numbers.sort();
+
+    let sorted: Vec<&str> = numbers.iter().map(|number| number.as_str().unwrap()).collect();
+    assert_eq!(sorted, vec!["1.25", "1.3", "2", "9", "10", "10a", "10.5", "010.50", "Extra"]);
+    assert_eq!(ChapterNumber::from("10.5").as_str(), Some("10.5"));
+    assert_eq!(ChapterNumber::from("  "), ChapterNumber::none());
+}
+
+#[test]
+fn unnumbered_chapters_are_placed() {
+    let mut list = numbered(&[Some("3"), None, Some("1"), Some(""), Some("2")]);
+    list.sort_chapters(UnnumberedPlacement::Last);
+    assert_eq!(numbers(&list), vec!["1", "2", "3", "unnumbered", "unnumbered"]);
+    // Unnumbered chapters keep the order they were sent in
+    assert_eq!(list.data[3].id, "chapter-1");
+
+    list.sort_chapters(UnnumberedPlacement::First);
+    assert_eq!(numbers(&list), vec!["unnumbered", "unnumbered", "1", "2", "3"]);
+    assert_eq!("first".parse::<UnnumberedPlacement>(), Ok(UnnumberedPlacement::First));
+}
+
+#[test]
+fn chapter_numbers_make_file_names() {
+    let list = numbered(&[Some("10.5"), Some("Extra 1"), None, None]);
+    let names: Vec<String> = list.data.iter().map(|chapter| chapter.generate_file_name()).collect();
+    assert_eq!(names, vec![
+        "Images/No_volume_10.5_ORDER.jpg",
+        "Images/No_volume_Extra_1_ORDER.jpg",
+        "Images/No_volume_unnumbered_chapter-2_ORDER.jpg",
+        "Images/No_volume_unnumbered_chapter-3_ORDER.jpg",
+    ]);
+}
@@ -0,0 +1,39 @@
+mod common;
+
+use std::time::Duration;
+
+use dexloader::client::DexClient;
+use dexloader::manga::Manga;
+
+use common::{Fault, MockServer, MANGA_URL};
+
+#[tokio::test]
+async fn never_exceeds_the_download_limit() {
+    let server = MockServer::start().await;
+    server.inject_times("/data/", Fault::Delay(Duration::from_millis(100)), 5);
+
+    let mut config = server.config();
+    config.concurrent_downloads = 2;
+    let mut manga = Manga::with_client(MANGA_URL, DexClient::new(config).unwrap());
+    manga.get_chapters(None, 0, None).await.unwrap();
+    manga.download_chapters(true, false).await.unwrap();
+
+    assert_eq!(server.count("/data/"), 5);
+    assert_eq!(server.max_active(), 2);
+}
+
+#[tokio::test]
+async fn keeps_pages_in_their_original_order() {
+    let server = MockServer::start().await;
+    let first_page = "/data/3f6a0c3bd5f4b2a1e9c8d7f6a5b4c3d2/1-";
+    // The first page only succeeds after a new server is requested
+    server.inject_times(first_page, Fault::Status(500), 4);
+
+    let mut manga = Manga::with_client(MANGA_URL, server.client());
+    manga.get_chapters(Some(1), 0, None).await.unwrap();
+    manga.download_chapters(true, false).await.unwrap();
+
+    let pages: Vec<i32> = manga.chapter_images[0].images.iter().map(|i| i.page_no).collect();
+    assert_eq!(pages, vec![0, 1, 2]);
+    assert_eq!(server.count("/at-home/server/"), 2);
+}
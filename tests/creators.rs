@@ -0,0 +1,60 @@
+mod common;
+
+use std::fs::File;
+use std::io::Read;
+
+use dexloader::manga::{CreatorRole, Manga};
+
+use common::{MockServer, ARTIST_ONLY_ID, MANGA_URL};
+
+#[tokio::test]
+async fn authors_and_artists_are_merged() {
+    let server = MockServer::start().await;
+    let mut manga = Manga::with_client(MANGA_URL, server.client());
+    manga.fetch_creators().await.unwrap();
+
+    assert_eq!(manga.creators.len(), 2);
+    assert_eq!(manga.creators[0].name, "Kotoyama");
+    assert_eq!(manga.creators[0].roles, vec![CreatorRole::Author, CreatorRole::Artist]);
+    assert_eq!(manga.creators[1].name, "Aoi & Co.");
+    assert_eq!(manga.creators[1].roles, vec![CreatorRole::Artist]);
+    assert_eq!(manga.author_name, "Kotoyama");
+    assert_eq!(server.count("/author/"), 0);
+}
+
+#[tokio::test]
+async fn creators_are_written_with_their_roles() {
+    let server = MockServer::start().await;
+    let out = tempfile::tempdir().unwrap();
+    let path = out.path().join("creators.epub");
+
+    let mut manga = Manga::with_client(MANGA_URL, server.client());
+    manga.get_chapters(None, 0, None).await.unwrap();
+    manga.download_chapters(true, false).await.unwrap();
+    manga.generate_epub(path.to_str().unwrap()).await.unwrap();
+
+    let mut archive = zip::ZipArchive::new(File::open(&path).unwrap()).unwrap();
+    assert_eq!(archive.by_index(0).unwrap().name(), "mimetype");
+    let mut package = String::new();
+    archive.by_name("OEBPS/content.opf").unwrap().read_to_string(&mut package).unwrap();
+
+    assert!(package.contains(r#"<dc:creator opf:role="aut">Kotoyama</dc:creator>"#));
+    assert!(package.contains(r#"<dc:creator opf:role="art">Kotoyama</dc:creator>"#));
+    assert!(package.contains(r#"<dc:creator opf:role="art">Aoi &amp; Co.</dc:creator>"#));
+    assert_eq!(package.matches("<dc:creator").count(), 3);
+}
+
+#[tokio::test]
+async fn creators_are_fetched_once_without_authors() {
+    let server = MockServer::start().await;
+    let mut manga = Manga::with_client(ARTIST_ONLY_ID, server.client());
+    manga.get_all_chapters().await.unwrap();
+    manga.download_range(0..1, true, false).await.unwrap();
+    assert_eq!(manga.author_name, "");
+    assert_eq!(manga.creators.len(), 2);
+
+    // Creators fetched again would replace the renamed one
+    manga.creators[0].name = String::from("Renamed");
+    manga.download_range(1..2, true, false).await.unwrap();
+    assert_eq!(manga.creators[0].name, "Renamed");
+}
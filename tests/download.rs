@@ -0,0 +1,48 @@
+mod common;
+
+use std::fs;
+use std::time::Duration;
+
+use dexloader::manga::Manga;
+
+use common::{Fault, MockServer, MANGA_URL};
+
+#[tokio::test]
+async fn downloads_chapters_and_generates_epub() {
+    let server = MockServer::start().await;
+    let mut manga = Manga::with_client(MANGA_URL, server.client());
+
+    manga.get_chapters(None, 0, None).await.unwrap();
+    assert_eq!(manga.get_total(), Some(2));
+
+    manga.download_chapters(true, true).await.unwrap();
+    assert_eq!(manga.title, "Yofukashi no Uta");
+    assert_eq!(manga.author_name, "Kotoyama");
+
+    let pages: Vec<usize> = manga.chapter_images.iter().map(|c| c.images.len()).collect();
+    assert_eq!(pages, vec![3, 2]);
+    manga.get_client().flush_reports().await;
+    assert_eq!(server.count("/report"), 5);
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("out.epub");
+    manga.generate_epub(path.to_str().unwrap()).await.unwrap();
+
+    let bytes = fs::read(&path).unwrap();
+    assert_eq!(&bytes[..2], b"PK");
+}
+
+#[tokio::test]
+async fn tolerates_slow_responses() {
+    let server = MockServer::start().await;
+    server.inject("/at-home/server/", Fault::Delay(Duration::from_millis(300)));
+    server.inject("/data/", Fault::Delay(Duration::from_millis(300)));
+
+    let mut manga = Manga::with_client(MANGA_URL, server.client());
+    manga.get_chapters(Some(1), 0, None).await.unwrap();
+    manga.download_chapters(true, false).await.unwrap();
+
+    assert_eq!(manga.chapter_images.len(), 1);
+    assert_eq!(manga.chapter_images[0].images.len(), 3);
+    assert_eq!(server.count("/report"), 0);
+}
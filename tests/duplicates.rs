@@ -0,0 +1,90 @@
+mod common;
+
+use dexloader::manga::{DuplicateStrategy, Manga, SelectionOptions};
+use serde_json::json;
+
+use common::{chapter_list, MockServer, MULTI_GROUP_URL, DAWN_PATROL_ID};
+
+/// Returns the ids of the uploads kept with a strategy
+async fn kept(strategy: DuplicateStrategy, preferred: &[&str]) -> Vec<String> {
+    let server = MockServer::start().await;
+    let selection = SelectionOptions {
+        duplicates: strategy,
+        preferred_groups: preferred.iter().map(|group| String::from(*group)).collect(),
+        ..SelectionOptions::default()
+    };
+    let mut manga = Manga::with_client(MULTI_GROUP_URL, server.client()).with_selection(selection);
+    manga.get_chapters(None, 0, None).await.unwrap();
+
+    manga.chapter_list.unwrap().data.iter()
+        .map(|chapter| chapter.id.clone())
+        .collect()
+}
+
+#[tokio::test]
+async fn keeps_the_upload_of_the_preferred_group() {
+    assert_eq!(kept(DuplicateStrategy::PreferredGroup, &[]).await, vec![
+        "b1b2c3d4-0000-4000-8000-000000000011",
+        "b1b2c3d4-0000-4000-8000-000000000021",
+    ]);
+    assert_eq!(kept(DuplicateStrategy::PreferredGroup, &[DAWN_PATROL_ID]).await, vec![
+        "b1b2c3d4-0000-4000-8000-000000000012",
+        "b1b2c3d4-0000-4000-8000-000000000022",
+    ]);
+}
+
+#[tokio::test]
+async fn keeps_the_upload_with_the_most_pages() {
+    assert_eq!(kept(DuplicateStrategy::MostPages, &[]).await, vec![
+        "b1b2c3d4-0000-4000-8000-000000000011",
+        "b1b2c3d4-0000-4000-8000-000000000022",
+    ]);
+}
+
+#[tokio::test]
+async fn keeps_the_newest_upload() {
+    assert_eq!(kept(DuplicateStrategy::NewestUpload, &[]).await, vec![
+        "b1b2c3d4-0000-4000-8000-000000000012",
+        "b1b2c3d4-0000-4000-8000-000000000021",
+    ]);
+}
+
+#[tokio::test]
+async fn keeps_every_upload() {
+    assert_eq!(kept(DuplicateStrategy::KeepAll, &[]).await.len(), 4);
+}
+
+#[test]
+fn uploads_are_only_duplicates_within_a_volume() {
+    let mut list = chapter_list(&[
+        json!({ "volume": "1", "chapter": "1" }),
+        json!({ "volume": "2", "chapter": "1" }),
+        json!({ "volume": "1", "chapter": "1" }),
+        json!({ "volume": null, "chapter": null }),
+        json!({ "volume": null, "chapter": null }),
+    ]);
+    list.remove_duplicates::<&str>(DuplicateStrategy::PreferredGroup, &[]);
+
+    let ids: Vec<&str> = list.data.iter().map(|chapter| chapter.id.as_str()).collect();
+    assert_eq!(ids, vec!["chapter-0", "chapter-1", "chapter-3", "chapter-4"]);
+}
+
+#[test]
+fn uploads_without_a_volume_are_duplicates_of_any_volume() {
+    let mut list = chapter_list(&[
+        json!({ "volume": "3", "chapter": "12" }),
+        json!({ "volume": null, "chapter": "12" }),
+        json!({ "volume": "4", "chapter": "13" }),
+    ]);
+    list.remove_duplicates::<&str>(DuplicateStrategy::PreferredGroup, &[]);
+
+    let ids: Vec<&str> = list.data.iter().map(|chapter| chapter.id.as_str()).collect();
+    assert_eq!(ids, vec!["chapter-0", "chapter-2"]);
+}
+
+#[test]
+fn strategies_are_parsed() {
+    assert_eq!("most-pages".parse::<DuplicateStrategy>(), Ok(DuplicateStrategy::MostPages));
+    assert_eq!("newest".parse::<DuplicateStrategy>(), Ok(DuplicateStrategy::NewestUpload));
+    assert!("longest".parse::<DuplicateStrategy>().is_err());
+}
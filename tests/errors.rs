@@ -0,0 +1,35 @@
+mod common;
+
+use dexloader::error::DexError;
+use dexloader::manga::Manga;
+
+use common::{Fault, MockServer, MANGA_URL};
+
+#[tokio::test]
+async fn missing_manga_is_not_found() {
+    let server = MockServer::start().await;
+    server.inject("/manga/", Fault::Status(404));
+
+    let mut manga = Manga::with_client(MANGA_URL, server.client());
+    match manga.fetch_data().await {
+        Err(DexError::NotFound(url)) => assert!(url.contains(common::MANGA_ID)),
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("expected an error"),
+    }
+}
+
+#[tokio::test]
+async fn server_errors_carry_the_mangadex_error_body() {
+    let server = MockServer::start().await;
+    server.inject(&common::feed_path(common::MANGA_ID), Fault::Status(503));
+
+    let mut manga = Manga::with_client(MANGA_URL, server.client());
+    match manga.get_chapters(None, 0, None).await {
+        Err(DexError::Http { status, errors, .. }) => {
+            assert_eq!(status, 503);
+            assert_eq!(errors.0[0].detail.as_deref(), Some("Injected fault"));
+        },
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("expected an error"),
+    }
+}
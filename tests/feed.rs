@@ -0,0 +1,89 @@
+mod common;
+
+use std::time::Duration;
+
+use futures::TryStreamExt;
+
+use dexloader::client::DexClient;
+use dexloader::manga::{Chapter, ChapterFeed, FeedOrder, Manga, SortDirection};
+use dexloader::rate_limit::Quota;
+
+use common::{feed_path, MockServer, LONG_ID, LONG_TOTAL, VOLUMES_ID};
+
+/// Returns a client whose requests are not held back by the rate
+/// limiter, for feeds of many pages
+fn unthrottled_client(server: &MockServer) -> DexClient {
+    let mut config = server.config();
+    config.rate_limits.api = Quota { requests: 1000, per: Duration::from_secs(1) };
+    DexClient::new(config).unwrap()
+}
+
+#[tokio::test]
+async fn every_page_is_requested_up_to_the_api_limit() {
+    let server = MockServer::start().await;
+    let mut manga = Manga::with_client(LONG_ID, unthrottled_client(&server));
+    manga.get_all_chapters().await.unwrap();
+
+    let chapters = &manga.chapter_list.as_ref().unwrap().data;
+    assert_eq!(chapters.len(), 10_000);
+    assert_eq!(chapters[0].get_chapter_number().to_string(), "1");
+    assert_eq!(chapters[9_999].get_chapter_number().to_string(), "10000");
+    assert_eq!(manga.get_total(), Some(LONG_TOTAL as i32));
+    assert_eq!(server.count(&feed_path(LONG_ID)), 100);
+}
+
+#[tokio::test]
+async fn a_range_spans_several_pages() {
+    let server = MockServer::start().await;
+    let mut manga = Manga::with_client(LONG_ID, unthrottled_client(&server));
+    manga.get_chapters(Some(250), 100, None).await.unwrap();
+
+    let chapters = &manga.chapter_list.as_ref().unwrap().data;
+    assert_eq!(chapters.len(), 250);
+    assert_eq!(chapters[0].get_chapter_number().to_string(), "101");
+    assert_eq!(chapters[249].get_chapter_number().to_string(), "350");
+
+    let limits: Vec<String> = server.requests()
+        .into_iter()
+        .filter(|r| r.path.starts_with(&feed_path(LONG_ID)))
+        .map(|r| r.path.split('&').next().unwrap().rsplit('=').next().unwrap().to_string())
+        .collect();
+    assert_eq!(limits, vec!["100", "100", "50"]);
+}
+
+#[tokio::test]
+async fn chapters_without_pages_are_left_out() {
+    let server = MockServer::start().await;
+    let chapters: Vec<Chapter> = ChapterFeed::new(server.client(), VOLUMES_ID)
+        .into_stream()
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(chapters.len(), 6);
+    assert!(chapters.iter().all(|chapter| chapter.get_pages() > 0));
+
+    let chapters: Vec<Chapter> = ChapterFeed::new(server.client(), VOLUMES_ID)
+        .with_empty_pages(true)
+        .into_stream()
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(chapters.len(), 7);
+}
+
+#[tokio::test]
+async fn filters_and_ordering_are_sent() {
+    let server = MockServer::start().await;
+    let feed = ChapterFeed::new(server.client(), VOLUMES_ID)
+        .with_languages(vec![String::from("en")])
+        .with_future_updates(false)
+        .with_order(FeedOrder::Volume, SortDirection::Descending)
+        .with_order(FeedOrder::Chapter, SortDirection::Ascending);
+    feed.collect().await.unwrap();
+
+    let request = server.requests().into_iter().find(|r| r.path.starts_with(&feed_path(VOLUMES_ID))).unwrap();
+    assert!(request.path.contains("translatedLanguage[]=en"));
+    assert!(request.path.contains("includeFutureUpdates=0"));
+    assert!(request.path.contains("includeEmptyPages=0"));
+    assert!(request.path.contains("order[volume]=desc&order[chapter]=asc"));
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use std::fs::File;
+use std::io::Read;
+
+use dexloader::manga::{DuplicateStrategy, Manga, SelectionOptions};
+
+use common::{MockServer, MULTI_GROUP_URL, DAWN_PATROL_ID, MIDNIGHT_CREW_ID, NIGHT_OWL_ID};
+
+fn manga_with(server: &MockServer, preferred: &[&str], blocked: &[&str]) -> Manga {
+    let selection = SelectionOptions {
+        preferred_groups: preferred.iter().map(|group| String::from(*group)).collect(),
+        blocked_groups: blocked.iter().map(|group| String::from(*group)).collect(),
+        duplicates: DuplicateStrategy::KeepAll,
+        ..SelectionOptions::default()
+    };
+    Manga::with_client(MULTI_GROUP_URL, server.client()).with_selection(selection)
+}
+
+/// Returns the chapter numbers and group ids of the chapter list
+fn uploads(manga: &Manga) -> Vec<(String, Vec<String>)> {
+    manga.chapter_list.as_ref().unwrap().data.iter()
+        .map(|chapter| {
+            let groups = chapter.get_group_ids().into_iter().map(String::from).collect();
+            (chapter.get_chapter_number().to_string(), groups)
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn chapters_carry_their_groups() {
+    let server = MockServer::start().await;
+    let mut manga = manga_with(&server, &[], &[]);
+    manga.get_chapters(None, 0, None).await.unwrap();
+
+    let chapters = &manga.chapter_list.as_ref().unwrap().data;
+    assert_eq!(chapters.len(), 4);
+    let joint = chapters.iter()
+        .find(|chapter| chapter.get_groups().len() == 2)
+        .unwrap();
+    let names: Vec<String> = joint.get_groups().into_iter().map(|group| group.name).collect();
+    assert_eq!(names, vec!["Midnight Crew", "Dawn Patrol"]);
+}
+
+#[tokio::test]
+async fn blocked_groups_are_skipped() {
+    let server = MockServer::start().await;
+    let mut manga = manga_with(&server, &[], &["midnight crew"]);
+    manga.get_chapters(None, 0, None).await.unwrap();
+
+    let uploads = uploads(&manga);
+    assert_eq!(uploads.len(), 3);
+    assert!(uploads.iter().all(|(_, groups)| !groups.iter().any(|group| group == MIDNIGHT_CREW_ID)));
+}
+
+#[tokio::test]
+async fn preferred_groups_come_first() {
+    let server = MockServer::start().await;
+    let mut manga = manga_with(&server, &[DAWN_PATROL_ID, "Night Owl Scans"], &[]);
+    manga.get_chapters(None, 0, None).await.unwrap();
+
+    let uploads = uploads(&manga);
+    assert_eq!(uploads[0], (String::from("1"), vec![String::from(DAWN_PATROL_ID)]));
+    assert_eq!(uploads[1], (String::from("1"), vec![String::from(NIGHT_OWL_ID)]));
+    assert_eq!(uploads[2], (String::from("2"), vec![String::from(MIDNIGHT_CREW_ID), String::from(DAWN_PATROL_ID)]));
+    assert_eq!(uploads[3], (String::from("2"), vec![String::from(NIGHT_OWL_ID)]));
+}
+
+#[tokio::test]
+async fn groups_are_credited_in_the_epub() {
+    let server = MockServer::start().await;
+    let out = tempfile::tempdir().unwrap();
+    let path = out.path().join("groups.epub");
+
+    let mut manga = manga_with(&server, &[], &[MIDNIGHT_CREW_ID]);
+    manga.get_chapters(None, 0, None).await.unwrap();
+    manga.download_chapters(true, false).await.unwrap();
+    manga.generate_epub(path.to_str().unwrap()).await.unwrap();
+
+    let mut archive = zip::ZipArchive::new(File::open(&path).unwrap()).unwrap();
+    let mut package = String::new();
+    archive.by_name("OEBPS/content.opf").unwrap().read_to_string(&mut package).unwrap();
+    assert!(package.contains(r#"<dc:contributor opf:role="trl">Night Owl Scans</dc:contributor>"#));
+    assert!(package.contains(r#"<dc:contributor opf:role="trl">Dawn Patrol</dc:contributor>"#));
+    assert!(!package.contains("Midnight Crew"));
+}
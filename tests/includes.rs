@@ -0,0 +1,41 @@
+mod common;
+
+use dexloader::manga::Manga;
+
+use common::{MockServer, MANGA_URL};
+
+#[tokio::test]
+async fn metadata_arrives_with_the_manga() {
+    let server = MockServer::start().await;
+    let mut manga = Manga::with_client(MANGA_URL, server.client());
+
+    let data = manga.fetch_data().await.unwrap();
+    let names: Vec<Option<&str>> = data.get_creator_relationships()
+        .iter()
+        .map(|relation| relation.get_name())
+        .collect();
+    assert_eq!(names, vec![Some("Kotoyama"), Some("Kotoyama"), Some("Aoi & Co.")]);
+    assert_eq!(data.get_cover_file_name(), Some("cover.png"));
+
+    manga.fetch_creators().await.unwrap();
+    manga.fetch_cover().await.unwrap();
+    assert_eq!(manga.author_name, "Kotoyama");
+    assert_eq!(server.count("/manga/"), 1);
+    assert_eq!(server.count("/author/"), 0);
+    assert_eq!(server.count("/cover/"), 0);
+    assert_eq!(server.count("/covers/"), 1);
+}
+
+#[tokio::test]
+async fn chapters_include_their_scanlation_groups() {
+    let server = MockServer::start().await;
+    let mut manga = Manga::with_client(MANGA_URL, server.client());
+    manga.get_chapters(None, 0, None).await.unwrap();
+
+    let chapter = &manga.chapter_list.as_ref().unwrap().data[0];
+    let group = chapter.get_relationships()
+        .iter()
+        .find(|relation| relation.relation_type == "scanlation_group")
+        .unwrap();
+    assert_eq!(group.get_name(), Some("Night Owl Scans"));
+}
@@ -0,0 +1,131 @@
+mod common;
+
+use std::fs::File;
+use std::io::Read;
+
+use dexloader::manga::{DuplicateStrategy, LanguageMode, Manga, SelectionOptions};
+use serde_json::json;
+
+use common::{chapter_list, feed_path, MockServer, LANGUAGES_ID, LANGUAGES_URL};
+
+/// Returns the ids and languages of the chapters kept
+fn kept(manga: &Manga) -> Vec<(String, String)> {
+    manga.chapter_list.as_ref().unwrap()
+        .data
+        .iter()
+        .map(|chapter| (chapter.id.clone(), String::from(chapter.get_language().unwrap())))
+        .collect()
+}
+
+#[tokio::test]
+async fn each_chapter_is_kept_in_the_best_language() {
+    let server = MockServer::start().await;
+    let selection = SelectionOptions {
+        languages: vec![String::from("pt-br"), String::from("es-la")],
+        ..SelectionOptions::default()
+    };
+    let mut manga = Manga::with_client(LANGUAGES_URL, server.client()).with_selection(selection);
+    manga.get_all_chapters().await.unwrap();
+
+    assert_eq!(kept(&manga), vec![
+        (String::from("d1b2c3d4-0000-4000-8000-000000000012"), String::from("pt-br")),
+        (String::from("d1b2c3d4-0000-4000-8000-000000000021"), String::from("pt-br")),
+    ]);
+    let request = server.requests().into_iter().find(|r| r.path.starts_with(&feed_path(LANGUAGES_ID))).unwrap();
+    assert!(request.path.contains("translatedLanguage[]=pt-br&translatedLanguage[]=es-la"));
+}
+
+#[tokio::test]
+async fn later_languages_fill_in_missing_chapters() {
+    let server = MockServer::start().await;
+    let selection = SelectionOptions {
+        languages: vec![String::from("en"), String::from("es-la")],
+        ..SelectionOptions::default()
+    };
+    let mut manga = Manga::with_client(LANGUAGES_URL, server.client()).with_selection(selection);
+    manga.get_all_chapters().await.unwrap();
+
+    assert_eq!(kept(&manga), vec![
+        (String::from("d1b2c3d4-0000-4000-8000-000000000013"), String::from("en")),
+        (String::from("d1b2c3d4-0000-4000-8000-000000000022"), String::from("es-la")),
+    ]);
+}
+
+#[test]
+fn languages_are_picked_within_a_volume() {
+    let mut list = chapter_list(&[
+        json!({ "volume": "1", "chapter": "1", "translatedLanguage": "es-la" }),
+        json!({ "volume": "2", "chapter": "1", "translatedLanguage": "en" }),
+        json!({ "volume": "1", "chapter": "1", "translatedLanguage": "en" }),
+    ]);
+    list.keep_best_language(&["en", "es-la"]);
+
+    let ids: Vec<&str> = list.data.iter().map(|chapter| chapter.id.as_str()).collect();
+    assert_eq!(ids, vec!["chapter-2", "chapter-1"]);
+}
+
+#[test]
+fn languages_are_picked_across_missing_volumes() {
+    let mut list = chapter_list(&[
+        json!({ "volume": null, "chapter": "12", "translatedLanguage": "en" }),
+        json!({ "volume": "3", "chapter": "12", "translatedLanguage": "es-la" }),
+        json!({ "volume": "3", "chapter": "13", "translatedLanguage": "es-la" }),
+    ]);
+    list.keep_best_language(&["en", "es-la"]);
+
+    let ids: Vec<&str> = list.data.iter().map(|chapter| chapter.id.as_str()).collect();
+    assert_eq!(ids, vec!["chapter-0", "chapter-2"]);
+}
+
+#[tokio::test]
+async fn original_language_only() {
+    let server = MockServer::start().await;
+    let selection = SelectionOptions {
+        language_mode: LanguageMode::Original,
+        ..SelectionOptions::default()
+    };
+    let mut manga = Manga::with_client(LANGUAGES_URL, server.client()).with_selection(selection);
+    manga.get_all_chapters().await.unwrap();
+
+    assert_eq!(kept(&manga), vec![
+        (String::from("d1b2c3d4-0000-4000-8000-000000000031"), String::from("ja")),
+    ]);
+}
+
+#[tokio::test]
+async fn all_languages() {
+    let server = MockServer::start().await;
+    let selection = SelectionOptions {
+        language_mode: LanguageMode::All,
+        duplicates: DuplicateStrategy::KeepAll,
+        ..SelectionOptions::default()
+    };
+    let mut manga = Manga::with_client(LANGUAGES_URL, server.client()).with_selection(selection);
+    manga.get_all_chapters().await.unwrap();
+
+    assert_eq!(manga.chapter_list.as_ref().unwrap().data.len(), 7);
+    let request = server.requests().into_iter().find(|r| r.path.starts_with(&feed_path(LANGUAGES_ID))).unwrap();
+    assert!(!request.path.contains("translatedLanguage"));
+}
+
+#[tokio::test]
+async fn language_is_written_to_the_epub() {
+    let server = MockServer::start().await;
+    let out = tempfile::tempdir().unwrap();
+    let path = out.path().join("languages.epub");
+
+    let selection = SelectionOptions {
+        languages: vec![String::from("es-la")],
+        ..SelectionOptions::default()
+    };
+    let mut manga = Manga::with_client(LANGUAGES_URL, server.client()).with_selection(selection);
+    manga.get_all_chapters().await.unwrap();
+    manga.download_chapters(true, false).await.unwrap();
+    assert_eq!(manga.get_language().as_deref(), Some("es-la"));
+    manga.generate_epub(path.to_str().unwrap()).await.unwrap();
+
+    let mut archive = zip::ZipArchive::new(File::open(&path).unwrap()).unwrap();
+    let mut package = String::new();
+    archive.by_name("OEBPS/content.opf").unwrap().read_to_string(&mut package).unwrap();
+    assert!(package.contains("<dc:language>es-la</dc:language>"));
+}
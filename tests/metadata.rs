@@ -0,0 +1,82 @@
+mod common;
+
+use std::fs::File;
+use std::io::Read;
+
+use dexloader::manga::{Manga, MangaData};
+use serde_json::json;
+
+use common::{MockServer, MANGA_URL};
+
+#[tokio::test]
+async fn attributes_are_parsed() {
+    let server = MockServer::start().await;
+    let mut manga = Manga::with_client(MANGA_URL, server.client());
+    let data = manga.fetch_data().await.unwrap();
+
+    assert_eq!(data.get_description(), Some("Kou Yamori has been unable to sleep at night, until he meets a vampire."));
+    assert_eq!(data.get_attributes().status.as_deref(), Some("ongoing"));
+    assert_eq!(data.get_attributes().year, Some(2019));
+    assert_eq!(data.get_tag_names(), vec!["Romance", "Supernatural"]);
+    assert_eq!(data.get_translated_languages(), vec!["en", "es-la"]);
+
+    let details = data.get_details();
+    let detail = |label: &str| details.iter()
+        .find(|(name, _)| name == label)
+        .map(|(_, value)| value.as_str());
+    assert_eq!(detail("Status"), Some("ongoing"));
+    assert_eq!(detail("Genre"), Some("Romance"));
+    assert_eq!(detail("Theme"), Some("Supernatural"));
+    assert_eq!(detail("Links"), Some("al: 107071, mu: 153154"));
+    assert_eq!(detail("Demographic"), Some("shounen"));
+}
+
+#[tokio::test]
+async fn epub_is_written_with_the_metadata() {
+    let server = MockServer::start().await;
+    let out = tempfile::tempdir().unwrap();
+    let path = out.path().join("metadata.epub");
+
+    let mut manga = Manga::with_client(MANGA_URL, server.client());
+    manga.get_chapters(None, 0, None).await.unwrap();
+    manga.download_chapters(true, false).await.unwrap();
+    manga.generate_epub(path.to_str().unwrap()).await.unwrap();
+
+    assert_eq!(server.count(&common::manga_path(common::MANGA_ID)), 1);
+
+    let mut archive = zip::ZipArchive::new(File::open(&path).unwrap()).unwrap();
+    let mut package = String::new();
+    archive.by_name("OEBPS/content.opf").unwrap().read_to_string(&mut package).unwrap();
+    assert!(package.contains("<dc:description>Kou Yamori has been unable to sleep at night, until he meets a vampire.</dc:description>"));
+    assert!(package.contains("<dc:subject>Romance</dc:subject>"));
+    assert!(package.contains("<dc:subject>Supernatural</dc:subject>"));
+
+    let mut details = String::new();
+    archive.by_name("OEBPS/Text/details.xhtml").unwrap().read_to_string(&mut details).unwrap();
+    assert!(details.contains("<p>Kou Yamori has been unable to sleep at night, until he meets a vampire.</p>"));
+    assert!(details.contains("<dt>Status</dt><dd>ongoing</dd>"));
+    assert!(details.contains("<dt>Genre</dt><dd>Romance</dd>"));
+    assert!(details.contains("<dt>Links</dt><dd>al: 107071, mu: 153154</dd>"));
+}
+
+/// The fixture manga with its main title replaced
+fn with_title(title: serde_json::Value) -> MangaData {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/manga.json");
+    let mut json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    json["data"]["attributes"]["title"] = title;
+    serde_json::from_value(json).unwrap()
+}
+
+#[test]
+fn title_follows_the_language_preference() {
+    let data = with_title(json!({"ja-ro": "Yofukashi no Uta", "ja": "よふかしのうた"}));
+    assert_eq!(data.get_title(&["en", "ja-ro", "ja"]), "Yofukashi no Uta");
+    assert_eq!(data.get_title(&["ja", "ja-ro"]), "よふかしのうた");
+
+    // Falls through to the alternative titles, then to any title
+    assert_eq!(data.get_title(&["en"]), "Call of the Night");
+    assert_eq!(data.get_title(&["ko"]), "よふかしのうた");
+
+    let data = with_title(json!([]));
+    assert_eq!(data.get_title(&["fr"]), "よふかしのうた");
+}
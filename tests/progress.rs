@@ -0,0 +1,82 @@
+mod common;
+
+use dexloader::manga::Manga;
+use dexloader::progress::Progress;
+use tokio::sync::mpsc;
+
+use common::{Fault, MockServer, MANGA_URL};
+
+fn drain(receiver: &mut mpsc::UnboundedReceiver<Progress>) -> Vec<Progress> {
+    let mut events = Vec::new();
+    while let Ok(event) = receiver.try_recv() {
+        events.push(event);
+    }
+    events
+}
+
+#[tokio::test]
+async fn reports_every_step_of_a_download_and_export() {
+    let server = MockServer::start().await;
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let client = server.client().with_progress(sender);
+
+    let mut manga = Manga::with_client(MANGA_URL, client);
+    manga.get_chapters(None, 0, None).await.unwrap();
+    manga.download_chapters(true, false).await.unwrap();
+    let events = drain(&mut receiver);
+
+    assert!(matches!(events[0], Progress::ChapterListFetched { count: 2, total: 2 }));
+    assert!(matches!(events[1], Progress::ChaptersScheduled { count: 2 }));
+    assert!(matches!(events[2], Progress::ChapterStarted { pages: 3, .. }));
+    let downloaded = events.iter()
+        .filter(|e| matches!(e, Progress::PageDownloaded { bytes, .. } if *bytes > 0))
+        .count();
+    let finished = events.iter()
+        .filter(|e| matches!(e, Progress::ChapterFinished { .. }))
+        .count();
+    assert_eq!(downloaded, 5);
+    assert_eq!(finished, 2);
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("out.epub");
+    manga.generate_epub(path.to_str().unwrap()).await.unwrap();
+    let events = drain(&mut receiver);
+
+    assert!(events.iter().any(|e| matches!(e, Progress::ExportStarted { pages: 5, .. })));
+    assert!(events.iter().any(|e| matches!(e, Progress::ExportProgress { written: 5, pages: 5 })));
+    assert!(matches!(events.last(), Some(Progress::ExportFinished { .. })));
+}
+
+#[tokio::test]
+async fn reports_retries() {
+    let server = MockServer::start().await;
+    server.inject("/data/", Fault::Status(500));
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let client = server.client().with_progress(sender);
+
+    let mut manga = Manga::with_client(MANGA_URL, client);
+    manga.get_chapters(Some(1), 0, None).await.unwrap();
+    manga.download_chapters(true, false).await.unwrap();
+
+    let events = drain(&mut receiver);
+    assert!(events.iter().any(|e| matches!(e, Progress::Retry { attempt: 1, .. })));
+}
+
+#[tokio::test]
+async fn reports_the_chapters_scheduled() {
+    let server = MockServer::start().await;
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    let client = server.client().with_progress(sender);
+
+    let mut manga = Manga::with_client(MANGA_URL, client);
+    manga.get_all_chapters().await.unwrap();
+    manga.download_range(1..2, true, false).await.unwrap();
+    let events = drain(&mut receiver);
+
+    assert!(matches!(events[0], Progress::ChapterListFetched { count: 2, .. }));
+    assert!(matches!(events[1], Progress::ChaptersScheduled { count: 1 }));
+    let started = events.iter()
+        .filter(|e| matches!(e, Progress::ChapterStarted { .. }))
+        .count();
+    assert_eq!(started, 1);
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use dexloader::client::DexClient;
+use dexloader::manga::{Manga, Quality};
+
+use common::{Fault, MockServer, MANGA_URL};
+
+async fn download_first_chapter(server: &MockServer, quality: Quality) -> Manga {
+    let mut config = server.config();
+    config.quality = quality;
+    let mut manga = Manga::with_client(MANGA_URL, DexClient::new(config).unwrap());
+    manga.get_chapters(Some(1), 0, None).await.unwrap();
+    manga.download_chapters(true, false).await.unwrap();
+    manga
+}
+
+#[tokio::test]
+async fn data_saver_downloads_compressed_pages() {
+    let server = MockServer::start().await;
+    let manga = download_first_chapter(&server, Quality::DataSaver).await;
+
+    assert_eq!(manga.chapter_images[0].images.len(), 3);
+    assert_eq!(server.count("/data-saver/"), 3);
+    assert_eq!(server.count("/data/"), 0);
+}
+
+#[tokio::test]
+async fn fallback_uses_data_saver_for_failing_pages() {
+    let server = MockServer::start().await;
+    let retries = common::fast_retry().max_retries as usize;
+    server.inject_times("/data/3f6a0c3bd5f4b2a1e9c8d7f6a5b4c3d2/1-", Fault::Status(500), retries + 1);
+
+    let manga = download_first_chapter(&server, Quality::OriginalWithFallback).await;
+
+    let pages: Vec<i32> = manga.chapter_images[0].images.iter().map(|i| i.page_no).collect();
+    assert_eq!(pages, vec![0, 1, 2]);
+    assert_eq!(server.count("/data-saver/"), 1);
+    assert_eq!(server.count("/at-home/server/"), 1);
+}
+
+/// Downloads the first chapter with a quality into a page cache,
+/// returning the server it was downloaded from
+async fn download_cached(cache: &std::path::Path, quality: Quality) -> MockServer {
+    let server = MockServer::start().await;
+    let mut config = server.config();
+    config.quality = quality;
+    config.cache_dir = Some(cache.to_path_buf());
+    let mut manga = Manga::with_client(MANGA_URL, DexClient::new(config).unwrap());
+    manga.get_chapters(Some(1), 0, None).await.unwrap();
+    manga.download_chapters(true, false).await.unwrap();
+    assert_eq!(manga.chapter_images[0].images.len(), 3);
+    server
+}
+
+#[tokio::test]
+async fn cached_chapters_are_only_reused_in_a_quality_that_meets_the_one_asked() {
+    let cache = tempfile::tempdir().unwrap();
+    download_cached(cache.path(), Quality::DataSaver).await;
+
+    let server = download_cached(cache.path(), Quality::OriginalWithFallback).await;
+    assert_eq!(server.count("/at-home/server/"), 0);
+
+    let server = download_cached(cache.path(), Quality::Original).await;
+    assert_eq!(server.count("/data/"), 3);
+
+    let server = download_cached(cache.path(), Quality::Original).await;
+    assert_eq!(server.count("/at-home/server/"), 0);
+}
@@ -0,0 +1,41 @@
+mod common;
+
+use std::time::{Duration, Instant};
+
+use dexloader::client::DexClient;
+use dexloader::manga::Manga;
+use dexloader::rate_limit::Quota;
+
+use common::{Fault, MockServer, MANGA_URL};
+
+#[tokio::test]
+async fn pauses_and_retries_when_rate_limited() {
+    let server = MockServer::start().await;
+    server.inject(&common::feed_path(common::MANGA_ID), Fault::RateLimited(1));
+
+    let start = Instant::now();
+    let mut manga = Manga::with_client(MANGA_URL, server.client());
+    manga.get_chapters(Some(1), 0, None).await.unwrap();
+    manga.download_chapters(true, false).await.unwrap();
+
+    assert_eq!(manga.title, "Yofukashi no Uta");
+    assert_eq!(server.count(&common::feed_path(common::MANGA_ID)), 2);
+    assert!(start.elapsed() >= Duration::from_millis(900));
+}
+
+#[tokio::test]
+async fn throttles_requests_to_the_quota() {
+    let server = MockServer::start().await;
+    let mut config = server.config();
+    config.rate_limits.api = Quota { requests: 2, per: Duration::from_millis(500) };
+    let mut manga = Manga::with_client(MANGA_URL, DexClient::new(config).unwrap());
+
+    let start = Instant::now();
+    for _ in 0..6 {
+        manga.get_chapters(Some(1), 0, None).await.unwrap();
+    }
+
+    // Two requests fit in the bucket, the other four wait 250ms each
+    assert!(start.elapsed() >= Duration::from_millis(900));
+    assert_eq!(server.count(&common::feed_path(common::MANGA_ID)), 6);
+}
@@ -0,0 +1,61 @@
+mod common;
+
+use dexloader::manga::Manga;
+use dexloader::report::Report;
+
+use common::{Fault, MockServer, MANGA_URL};
+
+const FIRST_PAGE: &str = "/data/3f6a0c3bd5f4b2a1e9c8d7f6a5b4c3d2/1-";
+
+async fn download_reported(server: &MockServer) -> Manga {
+    let mut manga = Manga::with_client(MANGA_URL, server.client());
+    manga.get_chapters(Some(1), 0, None).await.unwrap();
+    manga.download_chapters(true, true).await.unwrap();
+    manga.get_client().flush_reports().await;
+    manga
+}
+
+fn reports(server: &MockServer) -> Vec<Report> {
+    server.requests()
+        .iter()
+        .filter(|r| r.path == "/report")
+        .map(|r| serde_json::from_str(&r.body).unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn reports_failed_and_successful_pages() {
+    let server = MockServer::start().await;
+    server.inject(FIRST_PAGE, Fault::Status(500));
+    download_reported(&server).await;
+
+    let reports = reports(&server);
+    assert_eq!(reports.len(), 4);
+    assert_eq!(reports.iter().filter(|r| !r.success).count(), 1);
+    assert!(reports.iter().filter(|r| r.success).all(|r| r.bytes > 0));
+}
+
+#[tokio::test]
+async fn reports_connections_that_never_responded() {
+    let server = MockServer::start().await;
+    server.inject(FIRST_PAGE, Fault::Disconnect);
+    download_reported(&server).await;
+
+    let failed: Vec<Report> = reports(&server).into_iter().filter(|r| !r.success).collect();
+    assert_eq!(failed.len(), 1);
+    assert!(failed[0].url.contains("/1-"));
+    assert_eq!(failed[0].bytes, 0);
+}
+
+#[tokio::test]
+async fn rejected_reports_never_fail_the_download() {
+    let server = MockServer::start().await;
+    server.inject_times("/report", Fault::Status(500), 3);
+    let manga = download_reported(&server).await;
+
+    assert_eq!(manga.chapter_images[0].images.len(), 3);
+    let stats = manga.get_client().report_stats();
+    assert_eq!(stats.failed, 3);
+    assert_eq!(stats.sent, 0);
+    assert!(stats.last_error.is_some());
+}
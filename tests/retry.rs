@@ -0,0 +1,48 @@
+mod common;
+
+use dexloader::client::{DexClient, RetryPolicy};
+use dexloader::manga::Manga;
+
+use common::{Fault, MockServer, MANGA_URL};
+
+#[tokio::test]
+async fn retries_failed_pages_on_the_same_server() {
+    let server = MockServer::start().await;
+    server.inject_times("/data/", Fault::Status(500), 2);
+
+    let mut manga = Manga::with_client(MANGA_URL, server.client());
+    manga.get_chapters(Some(1), 0, None).await.unwrap();
+    manga.download_chapters(true, false).await.unwrap();
+
+    assert_eq!(manga.chapter_images[0].images.len(), 3);
+    assert_eq!(server.count("/data/"), 5);
+    assert_eq!(server.count("/at-home/server/"), 1);
+}
+
+#[tokio::test]
+async fn requests_a_new_server_after_repeated_failures() {
+    let server = MockServer::start().await;
+    let retries = common::fast_retry().max_retries as usize;
+    let first_page = "/data/3f6a0c3bd5f4b2a1e9c8d7f6a5b4c3d2/1-";
+    server.inject_times(first_page, Fault::Status(403), retries + 1);
+
+    let mut manga = Manga::with_client(MANGA_URL, server.client());
+    manga.get_chapters(Some(1), 0, None).await.unwrap();
+    manga.download_chapters(true, false).await.unwrap();
+
+    assert_eq!(manga.chapter_images[0].images.len(), 3);
+    assert_eq!(server.count("/at-home/server/"), 2);
+}
+
+#[tokio::test]
+async fn gives_up_when_retries_are_exhausted() {
+    let server = MockServer::start().await;
+    server.inject("/data/", Fault::Status(500));
+
+    let mut config = server.config();
+    config.retry = RetryPolicy::none();
+    let mut manga = Manga::with_client(MANGA_URL, DexClient::new(config).unwrap());
+    manga.get_chapters(Some(1), 0, None).await.unwrap();
+
+    assert!(manga.download_chapters(true, false).await.is_err());
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use std::fs::{self, File};
+use std::io::Read;
+
+use dexloader::client::{ClientConfig, DexClient};
+use dexloader::manga::{ImageData, Manga};
+
+use common::{MockServer, MANGA_URL};
+
+#[tokio::test]
+async fn spools_pages_to_disk() {
+    let server = MockServer::start().await;
+    let spool = tempfile::tempdir().unwrap();
+    let client = DexClient::new(ClientConfig {
+        spool_dir: Some(spool.path().to_path_buf()),
+        ..server.config()
+    }).unwrap();
+
+    let mut manga = Manga::with_client(MANGA_URL, client);
+    manga.get_chapters(None, 0, None).await.unwrap();
+    manga.download_chapters(true, false).await.unwrap();
+
+    let mut spooled = Vec::new();
+    for chapter in manga.chapter_images.iter() {
+        for page in chapter.images.iter() {
+            match &page.image {
+                ImageData::File(path) => {
+                    assert!(path.starts_with(spool.path()));
+                    spooled.push(fs::read(path).unwrap());
+                    assert!(image::load_from_memory(spooled.last().unwrap()).is_ok());
+                },
+                ImageData::Memory(_) => panic!("page {} was kept in memory", page.page_no),
+            }
+        }
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("out.epub");
+    manga.generate_epub(path.to_str().unwrap()).await.unwrap();
+
+    let bytes = fs::read(&path).unwrap();
+    assert_eq!(&bytes[..2], b"PK");
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+    // Pages are copied from the spool as they are
+    let mut archive = zip::ZipArchive::new(File::open(&path).unwrap()).unwrap();
+    let mut pages = Vec::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).unwrap();
+        if file.name().starts_with("OEBPS/Images/") && file.name() != "OEBPS/Images/cover.jpg" {
+            assert_eq!(file.compression(), zip::CompressionMethod::Stored);
+            let mut page = Vec::new();
+            file.read_to_end(&mut page).unwrap();
+            pages.push(page);
+        }
+    }
+    assert_eq!(pages.len(), spooled.len());
+    assert!(pages.iter().all(|page| spooled.contains(page)));
+}
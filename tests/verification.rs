@@ -0,0 +1,68 @@
+mod common;
+
+use dexloader::client::DexClient;
+use dexloader::error::DexError;
+use dexloader::manga::Manga;
+
+use common::{Fault, MockServer, MANGA_URL, SHORT_ID};
+
+const FIRST_PAGE: &str = "/data/3f6a0c3bd5f4b2a1e9c8d7f6a5b4c3d2/1-";
+
+#[tokio::test]
+async fn retries_truncated_pages() {
+    let server = MockServer::start().await;
+    server.inject(FIRST_PAGE, Fault::Truncate);
+
+    let mut manga = Manga::with_client(MANGA_URL, server.client());
+    manga.get_chapters(Some(1), 0, None).await.unwrap();
+    manga.download_chapters(true, false).await.unwrap();
+
+    assert_eq!(server.count(FIRST_PAGE), 2);
+    let first = &manga.chapter_images[0].images[0];
+    assert!(image::load_from_memory(&first.bytes().unwrap()).is_ok());
+}
+
+#[tokio::test]
+async fn accepts_pages_unchecked_when_verification_is_off() {
+    let server = MockServer::start().await;
+    server.inject(FIRST_PAGE, Fault::Truncate);
+
+    let mut config = server.config();
+    config.verify_pages = false;
+    let mut manga = Manga::with_client(MANGA_URL, DexClient::new(config).unwrap());
+    manga.get_chapters(Some(1), 0, None).await.unwrap();
+    manga.download_chapters(true, false).await.unwrap();
+
+    assert_eq!(server.count(FIRST_PAGE), 1);
+}
+
+#[tokio::test]
+async fn chapters_missing_pages_are_rejected() {
+    let server = MockServer::start().await;
+    let mut manga = Manga::with_client(SHORT_ID, server.client());
+    manga.get_chapters(None, 0, None).await.unwrap();
+
+    match manga.download_chapters(true, false).await {
+        Err(DexError::Integrity { reason, .. }) => assert!(reason.contains("4 pages")),
+        Err(e) => panic!("unexpected error: {}", e),
+        Ok(_) => panic!("expected an error"),
+    }
+    assert_eq!(server.count("/data/"), 0);
+
+    let mut config = server.config();
+    config.verify_pages = false;
+    let mut manga = Manga::with_client(SHORT_ID, DexClient::new(config).unwrap());
+    manga.get_chapters(None, 0, None).await.unwrap();
+    manga.download_chapters(true, false).await.unwrap();
+    assert_eq!(manga.chapter_images[0].images.len(), 3);
+}
+
+#[tokio::test]
+async fn images_without_a_hash_are_still_decoded() {
+    let server = MockServer::start().await;
+    server.inject("/covers/", Fault::Truncate);
+
+    let mut manga = Manga::with_client(MANGA_URL, server.client());
+    assert!(matches!(manga.fetch_cover().await, Err(DexError::ImageDecode(_))));
+    assert!(manga.fetch_cover().await.is_ok());
+}
@@ -0,0 +1,84 @@
+mod common;
+
+use std::fs::File;
+use std::io::Read;
+
+use dexloader::manga::{Manga, UnnumberedPlacement, Volume};
+use serde_json::json;
+
+use common::{chapter_list, MockServer, VOLUMES_URL};
+
+#[tokio::test]
+async fn chapters_are_grouped_by_volume() {
+    let server = MockServer::start().await;
+    let mut manga = Manga::with_client(VOLUMES_URL, server.client());
+    manga.get_all_chapters().await.unwrap();
+
+    assert_eq!(manga.get_volumes(), vec![
+        Volume::from("1"),
+        Volume::from("2"),
+        Volume::from("10"),
+        Volume::unassigned(),
+    ]);
+
+    let volumes: Vec<(String, Vec<String>)> = manga.chapter_list.as_ref().unwrap()
+        .group_by_volume()
+        .into_iter()
+        .map(|(volume, chapters)| {
+            let numbers = chapters.iter().map(|chapter| chapter.get_chapter_number().to_string()).collect();
+            (volume.to_string(), numbers)
+        })
+        .collect();
+    assert_eq!(volumes, vec![
+        (String::from("Volume 1"), vec![String::from("1"), String::from("2")]),
+        (String::from("Volume 2"), vec![String::from("3")]),
+        (String::from("Volume 10"), vec![String::from("10")]),
+        (String::from("No volume"), vec![String::from("11"), String::from("12")]),
+    ]);
+}
+
+#[tokio::test]
+async fn writes_one_epub_per_volume() {
+    let server = MockServer::start().await;
+    let out = tempfile::tempdir().unwrap();
+    let mut manga = Manga::with_client(VOLUMES_URL, server.client());
+    manga.get_all_chapters().await.unwrap();
+
+    for volume in manga.get_volumes() {
+        manga.download_volume(&volume, true, false).await.unwrap();
+        let path = out.path().join(format!("{}.epub", volume.to_file_name()));
+        manga.generate_volume_epub(&volume, path.to_str().unwrap()).await.unwrap();
+    }
+
+    let mut names: Vec<String> = std::fs::read_dir(out.path()).unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+    assert_eq!(names, vec!["No_volume.epub", "Vol_1.epub", "Vol_10.epub", "Vol_2.epub"]);
+    // The cover is only downloaded once, without a response cache
+    assert_eq!(server.count("/covers/"), 1);
+
+    // The unassigned chapters have 2 and 3 pages
+    let path = out.path().join("No_volume.epub");
+    let mut archive = zip::ZipArchive::new(File::open(&path).unwrap()).unwrap();
+    let pages = archive.file_names().filter(|name| name.starts_with("OEBPS/Images/No_volume_")).count();
+    assert_eq!(pages, 5);
+    let mut package = String::new();
+    archive.by_name("OEBPS/content.opf").unwrap().read_to_string(&mut package).unwrap();
+    assert!(package.contains("<dc:title>Yofukashi no Uta - No volume</dc:title>"));
+}
+
+#[test]
+fn chapters_are_ordered_by_volume_when_numbers_start_over() {
+    let mut list = chapter_list(&[
+        json!({ "volume": "2", "chapter": "1" }),
+        json!({ "volume": "1", "chapter": "2" }),
+        json!({ "volume": "1", "chapter": "1" }),
+        json!({ "volume": "2", "chapter": "2" }),
+        json!({ "volume": null, "chapter": "3" }),
+    ]);
+    list.sort_chapters(UnnumberedPlacement::Last);
+
+    let ids: Vec<&str> = list.data.iter().map(|chapter| chapter.id.as_str()).collect();
+    assert_eq!(ids, vec!["chapter-2", "chapter-1", "chapter-0", "chapter-3", "chapter-4"]);
+}